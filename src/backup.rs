@@ -0,0 +1,217 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::unix_now;
+use crate::installed::InstalledVersion;
+use crate::state::STATE_FILE_NAME;
+
+/// インストール先に置くバックアップのディレクトリ名。
+pub const BACKUP_DIR_NAME: &str = ".mc-velocity-installer-backup";
+
+const BACKUP_FILE_NAME: &str = "backup.json";
+
+/// ロールバックで書き戻さなかった `velocity.toml` の控えを置く名前。
+const TOML_BEFORE_UPGRADE_NAME: &str = "velocity.toml.before-upgrade";
+
+/// jar のほかにバックアップするファイル。
+const BACKED_UP_FILES: [&str; 4] = ["start.sh", "start.bat", "velocity.toml", STATE_FILE_NAME];
+
+/// アップグレード直前の jar・起動スクリプト・`velocity.toml`・状態ファイルの控え。
+///
+/// 控えは直前の 1 組だけを持ち、アップグレードのたびに置き換える。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backup {
+    /// 控えを取った時点のバージョン（表示用）。
+    pub label: String,
+    /// 起動スクリプトに書かれていた jar（書かれたまま）。
+    pub jar: String,
+    /// 控えに含めたファイル（jar を除く）。
+    pub files: Vec<String>,
+    /// 控えを取った日時（UNIX 秒）。
+    pub created_at: u64,
+}
+
+impl Backup {
+    pub fn dir(install_dir: &Path) -> PathBuf {
+        install_dir.join(BACKUP_DIR_NAME)
+    }
+
+    /// `installed` の控えを取る。以前の控えは削除する。
+    pub fn create(
+        install_dir: &Path,
+        installed: &InstalledVersion,
+    ) -> Result<Self, Box<dyn Error>> {
+        if !installed.jar_path.exists() {
+            return Err(format!("jar が見つかりません: {}", installed.jar_path.display()).into());
+        }
+        let dir = Self::dir(install_dir);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+        fs::copy(
+            &installed.jar_path,
+            dir.join(jar_file_name(&installed.jar)?),
+        )?;
+        let mut files = Vec::new();
+        for name in BACKED_UP_FILES {
+            let path = install_dir.join(name);
+            if path.exists() {
+                fs::copy(&path, dir.join(name))?;
+                files.push(name.to_string());
+            }
+        }
+        let backup = Self {
+            label: installed.label(),
+            jar: installed.jar.clone(),
+            files,
+            created_at: unix_now(),
+        };
+        let mut contents = serde_json::to_string_pretty(&backup)?;
+        contents.push('\n');
+        fs::write(dir.join(BACKUP_FILE_NAME), contents)?;
+        Ok(backup)
+    }
+
+    /// 控えが無い場合は `None`。
+    pub fn load(install_dir: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        let path = Self::dir(install_dir).join(BACKUP_FILE_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path)?;
+        let backup = serde_json::from_str(&contents)
+            .map_err(|err| format!("バックアップの形式が不正です: {} ({err})", path.display()))?;
+        Ok(Some(backup))
+    }
+
+    /// 控えをインストール先に書き戻し、控えを削除する。
+    ///
+    /// アップグレードで追加された jar は残す。控えに状態ファイルが無ければ、今の状態ファイルを削除する。
+    /// `velocity.toml` がアップグレード後に変更されていれば上書きせず、控えを別名で残してその場所を返す。
+    pub fn restore(&self, install_dir: &Path) -> Result<Option<PathBuf>, Box<dyn Error>> {
+        let dir = Self::dir(install_dir);
+        let jar_path = if Path::new(&self.jar).is_absolute() {
+            PathBuf::from(&self.jar)
+        } else {
+            install_dir.join(&self.jar)
+        };
+        fs::copy(dir.join(jar_file_name(&self.jar)?), jar_path)?;
+        let mut toml_before_upgrade = None;
+        for name in &self.files {
+            let backed_up = dir.join(name);
+            let path = install_dir.join(name);
+            // アップグレードは velocity.toml を書き換えないため、違いはその後の変更
+            if name == "velocity.toml" && path.exists() {
+                if fs::read(&path)? != fs::read(&backed_up)? {
+                    let aside = install_dir.join(TOML_BEFORE_UPGRADE_NAME);
+                    fs::copy(&backed_up, &aside)?;
+                    toml_before_upgrade = Some(aside);
+                }
+                continue;
+            }
+            fs::copy(&backed_up, &path)?;
+        }
+        let state_path = install_dir.join(STATE_FILE_NAME);
+        if !self.files.iter().any(|name| name == STATE_FILE_NAME) && state_path.exists() {
+            fs::remove_file(&state_path)?;
+        }
+        fs::remove_dir_all(&dir)?;
+        Ok(toml_before_upgrade)
+    }
+}
+
+fn jar_file_name(jar: &str) -> Result<&std::ffi::OsStr, Box<dyn Error>> {
+    Path::new(jar)
+        .file_name()
+        .ok_or_else(|| format!("jar 名を取得できません: {jar}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install(dir: &Path, files: &[(&str, &str)]) -> InstalledVersion {
+        fs::write(dir.join("velocity.jar"), b"old-jar").expect("write jar");
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).expect("write file");
+        }
+        InstalledVersion {
+            install_dir: dir.to_path_buf(),
+            script: "start.sh".to_string(),
+            jar: "velocity.jar".to_string(),
+            jar_path: dir.join("velocity.jar"),
+            version: Some("1.0.0".to_string()),
+            build: None,
+            detected_from: None,
+        }
+    }
+
+    fn read(path: PathBuf) -> String {
+        fs::read_to_string(path).expect("read file")
+    }
+
+    #[test]
+    fn restore_keeps_velocity_toml_edited_after_upgrade() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let dir = temp_dir.path();
+        let installed = install(
+            dir,
+            &[
+                ("start.sh", "java -jar velocity.jar\n"),
+                ("velocity.toml", "bind = \"0.0.0.0:25577\"\n"),
+            ],
+        );
+        let backup = Backup::create(dir, &installed).expect("create backup");
+
+        fs::write(dir.join("velocity.jar"), b"new-jar").expect("write jar");
+        fs::write(dir.join("start.sh"), "java -Xmx1G -jar velocity.jar\n").expect("write script");
+        fs::write(dir.join("velocity.toml"), "bind = \"0.0.0.0:25565\"\n").expect("edit toml");
+
+        let aside = backup
+            .restore(dir)
+            .expect("restore")
+            .expect("toml kept aside");
+        assert_eq!(
+            fs::read(dir.join("velocity.jar")).expect("read jar"),
+            b"old-jar"
+        );
+        assert_eq!(read(dir.join("start.sh")), "java -jar velocity.jar\n");
+        assert_eq!(
+            read(dir.join("velocity.toml")),
+            "bind = \"0.0.0.0:25565\"\n"
+        );
+        assert_eq!(aside, dir.join(TOML_BEFORE_UPGRADE_NAME));
+        assert_eq!(read(aside), "bind = \"0.0.0.0:25577\"\n");
+        assert!(!Backup::dir(dir).exists());
+
+        // 変更されていなければ別名の控えは作らない
+        let backup = Backup::create(dir, &installed).expect("create backup");
+        fs::remove_file(dir.join(TOML_BEFORE_UPGRADE_NAME)).expect("remove aside");
+        assert!(backup.restore(dir).expect("restore").is_none());
+        assert!(!dir.join(TOML_BEFORE_UPGRADE_NAME).exists());
+    }
+
+    #[test]
+    fn restore_removes_state_file_created_by_upgrade() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let dir = temp_dir.path();
+        let installed = install(dir, &[("start.sh", "java -jar velocity.jar\n")]);
+        let backup = Backup::create(dir, &installed).expect("create backup");
+        assert!(!backup.files.iter().any(|name| name == STATE_FILE_NAME));
+
+        fs::write(dir.join(STATE_FILE_NAME), "{}").expect("write state");
+        backup.restore(dir).expect("restore");
+        assert!(!dir.join(STATE_FILE_NAME).exists());
+
+        // 控えに状態ファイルがあれば、その内容に戻す
+        fs::write(dir.join(STATE_FILE_NAME), "{\"version\":\"1.0.0\"}").expect("write state");
+        let backup = Backup::create(dir, &installed).expect("create backup");
+        fs::write(dir.join(STATE_FILE_NAME), "{\"version\":\"1.1.0\"}").expect("write state");
+        backup.restore(dir).expect("restore");
+        assert_eq!(read(dir.join(STATE_FILE_NAME)), "{\"version\":\"1.0.0\"}");
+    }
+}
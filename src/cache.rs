@@ -0,0 +1,440 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::checksum::{Algorithm, Checksum};
+
+const CACHE_DIR_NAME: &str = "mc-velocity-installer";
+
+/// キャッシュの置き場所。`$XDG_CACHE_HOME/mc-velocity-installer`（未設定なら `~/.cache` 配下）。
+///
+/// jar は `jars/`、バージョン一覧は `index/` に保存する。
+pub fn default_cache_dir() -> Option<PathBuf> {
+    cache_home().map(|dir| dir.join(CACHE_DIR_NAME))
+}
+
+/// 検証済みの jar をチェックサムごとに保存するキャッシュ。
+///
+/// `<dir>/<sha512 か sha256>/<jar名>` の形で保存し、使うたびに更新日時を現在時刻にする。
+/// 衝突を作れる sha1 は鍵に使わない。
+#[derive(Debug, Clone)]
+pub struct JarCache {
+    dir: PathBuf,
+}
+
+/// キャッシュ内の jar 1 件。
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    /// 保存に使ったチェックサム（sha512 か sha256 の16進数）。
+    pub digest: String,
+    pub path: PathBuf,
+    pub size: u64,
+    /// 最後に保存・利用した日時。
+    pub modified: SystemTime,
+}
+
+impl JarCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// キャッシュの置き場所の `jars/`。
+    pub fn in_cache_dir(cache_dir: &Path) -> Self {
+        Self::new(cache_dir.join("jars"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// チェックサムが一致する jar があれば返す。内容が壊れていた場合は削除して `None` を返す。
+    pub fn lookup(&self, checksum: &Checksum) -> Option<PathBuf> {
+        let digest = checksum.value.to_ascii_lowercase();
+        let entry_dir = self.dir.join(&digest);
+        let path = jar_in(&entry_dir)?;
+        match checksum.algorithm.digest_file(&path) {
+            Ok(actual) if actual == digest => {
+                let _ = touch(&path);
+                Some(path)
+            }
+            _ => {
+                let _ = fs::remove_dir_all(&entry_dir);
+                None
+            }
+        }
+    }
+
+    /// 検証済みの `src` を `jar_name` としてキャッシュに保存する。
+    ///
+    /// 署名を検証した場合は、次に使うときに検証し直せるよう署名も保存する。
+    pub fn store(
+        &self,
+        checksum: &Checksum,
+        jar_name: &str,
+        src: &Path,
+        signature: Option<&str>,
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        let entry_dir = self.dir.join(checksum.value.to_ascii_lowercase());
+        fs::create_dir_all(&entry_dir)?;
+        let path = entry_dir.join(jar_name);
+        let signature_path = signature_path(&path);
+        match signature {
+            Some(signature) => fs::write(&signature_path, signature)?,
+            None if signature_path.exists() => fs::remove_file(&signature_path)?,
+            None => {}
+        }
+        let temp_path = entry_dir.join(format!(".{jar_name}.tmp"));
+        fs::copy(src, &temp_path)?;
+        fs::rename(&temp_path, &path)?;
+        Ok(path)
+    }
+
+    /// `store` で一緒に保存した署名。
+    pub fn signature(&self, jar: &Path) -> Option<String> {
+        fs::read_to_string(signature_path(jar)).ok()
+    }
+
+    /// 新しく使った順に並べたキャッシュの一覧。
+    pub fn entries(&self) -> Result<Vec<CacheEntry>, Box<dyn Error>> {
+        let mut entries = Vec::new();
+        if !self.dir.exists() {
+            return Ok(entries);
+        }
+        for item in fs::read_dir(&self.dir)? {
+            let item = item?;
+            let digest = item.file_name().to_string_lossy().to_string();
+            if digest_algorithm(&digest).is_none() {
+                continue;
+            }
+            let Some(path) = jar_in(&item.path()) else {
+                continue;
+            };
+            let metadata = fs::metadata(&path)?;
+            entries.push(CacheEntry {
+                digest,
+                path,
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            });
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.modified));
+        Ok(entries)
+    }
+
+    /// `older_than` より長く使っていない jar、内容が壊れた jar、保存途中の残骸を削除する。
+    pub fn prune(&self, older_than: Duration) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let mut removed = Vec::new();
+        if !self.dir.exists() {
+            return Ok(removed);
+        }
+        let now = SystemTime::now();
+        for item in fs::read_dir(&self.dir)? {
+            let entry_dir = item?.path();
+            if !entry_dir.is_dir() {
+                continue;
+            }
+            let digest = entry_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let keep = digest_algorithm(&digest).is_some_and(|algorithm| {
+                jar_in(&entry_dir).is_some_and(|path| {
+                    let recent = fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .is_ok_and(|modified| {
+                            now.duration_since(modified).unwrap_or_default() <= older_than
+                        });
+                    recent
+                        && algorithm
+                            .digest_file(&path)
+                            .is_ok_and(|actual| actual == digest)
+                })
+            });
+            if !keep {
+                fs::remove_dir_all(&entry_dir)?;
+                removed.push(entry_dir);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// キャッシュをすべて削除し、削除した jar の数を返す。
+    pub fn clear(&self) -> Result<usize, Box<dyn Error>> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+        let count = self.entries()?.len();
+        fs::remove_dir_all(&self.dir)?;
+        Ok(count)
+    }
+}
+
+/// バージョン一覧の応答を、ETag / Last-Modified と合わせて URL ごとに保存するキャッシュ。
+#[derive(Debug, Clone)]
+pub struct IndexCache {
+    dir: PathBuf,
+    /// 通信せずに保存済みの応答だけを使う。
+    offline: bool,
+}
+
+/// 保存した応答。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    /// 最後に取得（または再検証）した日時（UNIX 秒）。
+    pub fetched_at: u64,
+    pub body: String,
+}
+
+impl IndexCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            offline: false,
+        }
+    }
+
+    /// キャッシュの置き場所の `index/`。
+    pub fn in_cache_dir(cache_dir: &Path) -> Self {
+        Self::new(cache_dir.join("index"))
+    }
+
+    /// 同じ場所を使い、通信しない（保存済みの応答だけを使う）キャッシュ。
+    pub fn offline(&self) -> Self {
+        Self {
+            dir: self.dir.clone(),
+            offline: true,
+        }
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    pub fn load(&self, url: &str) -> Option<CachedResponse> {
+        let contents = fs::read_to_string(self.path(url)).ok()?;
+        serde_json::from_str::<CachedResponse>(&contents)
+            .ok()
+            .filter(|cached| cached.url == url)
+    }
+
+    pub fn save(&self, response: &CachedResponse) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(&response.url);
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string(response)?)?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    /// 保存した応答をすべて削除する。
+    pub fn clear(&self) -> Result<(), Box<dyn Error>> {
+        if self.dir.exists() {
+            fs::remove_dir_all(&self.dir)?;
+        }
+        Ok(())
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:x}.json", Sha256::digest(url.as_bytes())))
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// UNIX 秒を `2024-01-02 03:04:05 UTC` の形式にする。
+pub fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let time = seconds % 86_400;
+    // 1970-01-01 からの日数を年月日に変換する（proleptic グレゴリオ暦）。
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// エントリのディレクトリにある jar（保存途中の `.` で始まるファイルは除く）。
+fn jar_in(entry_dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(entry_dir)
+        .ok()?
+        .filter_map(|item| item.ok())
+        .map(|item| item.path())
+        .find(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .is_some_and(|name| !name.to_string_lossy().starts_with('.'))
+        })
+}
+
+/// jar と同じディレクトリの `.<jar名>.sig`（`jar_in` が jar と取り違えないよう隠しファイルにする）。
+fn signature_path(jar: &Path) -> PathBuf {
+    let name = jar
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    jar.with_file_name(format!(".{name}.sig"))
+}
+
+/// キャッシュのディレクトリ名から、保存に使ったアルゴリズムを桁数で判別する。
+fn digest_algorithm(name: &str) -> Option<Algorithm> {
+    [Algorithm::Sha512, Algorithm::Sha256]
+        .into_iter()
+        .find(|algorithm| algorithm.is_valid_hex(name))
+}
+
+fn touch(path: &Path) -> std::io::Result<()> {
+    File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+fn cache_home() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(home) = std::env::var_os("HOME").filter(|home| !home.is_empty()) {
+        return Some(PathBuf::from(home).join(".cache"));
+    }
+    std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JAR_BYTES: &[u8] = b"velocity-jar";
+
+    fn store_jar(cache: &JarCache, temp_dir: &Path) -> (Checksum, PathBuf) {
+        let checksum = Checksum {
+            algorithm: Algorithm::Sha256,
+            value: format!("{:x}", Sha256::digest(JAR_BYTES)),
+        };
+        let src = temp_dir.join("velocity.jar");
+        fs::write(&src, JAR_BYTES).expect("write jar");
+        let path = cache
+            .store(&checksum, "velocity.jar", &src, None)
+            .expect("store jar");
+        (checksum, path)
+    }
+
+    #[test]
+    fn lookup_returns_stored_jar() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let cache = JarCache::new(temp_dir.path().join("cache"));
+        let (checksum, path) = store_jar(&cache, temp_dir.path());
+
+        let uppercase = Checksum {
+            value: checksum.value.to_ascii_uppercase(),
+            ..checksum.clone()
+        };
+        assert_eq!(cache.lookup(&uppercase), Some(path));
+        let unknown = Checksum {
+            value: "0".repeat(64),
+            ..checksum.clone()
+        };
+        assert_eq!(cache.lookup(&unknown), None);
+        let entries = cache.entries().expect("entries");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].digest, checksum.value);
+        assert_eq!(entries[0].size, JAR_BYTES.len() as u64);
+    }
+
+    #[test]
+    fn lookup_discards_corrupted_jar() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let cache = JarCache::new(temp_dir.path().join("cache"));
+        let (checksum, path) = store_jar(&cache, temp_dir.path());
+        fs::write(&path, b"corrupted").expect("corrupt jar");
+
+        assert_eq!(cache.lookup(&checksum), None);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn prune_removes_unused_and_broken_entries() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let cache = JarCache::new(temp_dir.path().join("cache"));
+        let (_, path) = store_jar(&cache, temp_dir.path());
+        let leftover = cache.dir().join("f".repeat(64));
+        fs::create_dir_all(&leftover).expect("leftover dir");
+        fs::write(leftover.join(".velocity.jar.tmp"), b"partial").expect("leftover");
+
+        let removed = cache.prune(Duration::from_secs(3600)).expect("prune");
+        assert_eq!(removed, vec![leftover]);
+        assert!(path.exists());
+
+        File::options()
+            .write(true)
+            .open(&path)
+            .expect("open jar")
+            .set_modified(SystemTime::now() - Duration::from_secs(7200))
+            .expect("set modified");
+        let removed = cache.prune(Duration::from_secs(3600)).expect("prune");
+        assert_eq!(removed.len(), 1);
+        assert!(cache.entries().expect("entries").is_empty());
+    }
+
+    #[test]
+    fn index_cache_round_trips_by_url() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let cache = IndexCache::in_cache_dir(temp_dir.path());
+        let response = CachedResponse {
+            url: "https://example.com/velocity.json".to_string(),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            fetched_at: 1_700_000_000,
+            body: "{}".to_string(),
+        };
+        cache.save(&response).expect("save");
+
+        let loaded = cache.load(&response.url).expect("cached response");
+        assert_eq!(loaded.etag, response.etag);
+        assert_eq!(loaded.body, "{}");
+        assert!(cache.load("https://example.com/other.json").is_none());
+    }
+
+    #[test]
+    fn format_timestamp_uses_utc_calendar() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(1_709_210_096), "2024-02-29 12:34:56 UTC");
+    }
+
+    #[test]
+    fn clear_removes_everything() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let cache = JarCache::new(temp_dir.path().join("cache"));
+        store_jar(&cache, temp_dir.path());
+
+        assert_eq!(cache.clear().expect("clear"), 1);
+        assert!(!cache.dir().exists());
+        assert_eq!(cache.clear().expect("clear"), 0);
+    }
+}
@@ -0,0 +1,215 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+/// 検証に使うハッシュアルゴリズム。強い順に並べている。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha512,
+    Sha256,
+    /// 衝突耐性が無いため、明示的に許可した場合だけ使う。
+    Sha1,
+}
+
+impl Algorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sha512 => "sha512",
+            Self::Sha256 => "sha256",
+            Self::Sha1 => "sha1",
+        }
+    }
+
+    /// 16進数で表した場合の桁数。
+    pub fn hex_len(self) -> usize {
+        match self {
+            Self::Sha512 => 128,
+            Self::Sha256 => 64,
+            Self::Sha1 => 40,
+        }
+    }
+
+    pub fn is_weak(self) -> bool {
+        self == Self::Sha1
+    }
+
+    pub fn is_valid_hex(self, value: &str) -> bool {
+        value.len() == self.hex_len() && value.chars().all(|c| c.is_ascii_hexdigit())
+    }
+
+    /// ファイルのハッシュ（小文字の16進数）。
+    pub fn digest_file(self, path: &Path) -> Result<String, Box<dyn Error>> {
+        match self {
+            Self::Sha512 => digest_file::<Sha512>(path),
+            Self::Sha256 => digest_file::<Sha256>(path),
+            Self::Sha1 => digest_file::<Sha1>(path),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn digest_file<D: Digest>(path: &Path) -> Result<String, Box<dyn Error>> {
+    let mut file = File::open(path)?;
+    let mut hasher = D::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// 一覧や指定で与えられたチェックサム。JSON では `sha256` などの項目として読み書きする。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Checksums {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha512: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+}
+
+impl Checksums {
+    pub fn sha256(value: &str) -> Self {
+        Self {
+            sha256: Some(value.to_string()),
+            ..Self::default()
+        }
+    }
+
+    pub fn get(&self, algorithm: Algorithm) -> Option<&str> {
+        match algorithm {
+            Algorithm::Sha512 => self.sha512.as_deref(),
+            Algorithm::Sha256 => self.sha256.as_deref(),
+            Algorithm::Sha1 => self.sha1.as_deref(),
+        }
+    }
+
+    /// 値があるアルゴリズムと値を強い順に返す。
+    pub fn iter(&self) -> impl Iterator<Item = (Algorithm, &str)> {
+        [Algorithm::Sha512, Algorithm::Sha256, Algorithm::Sha1]
+            .into_iter()
+            .filter_map(|algorithm| self.get(algorithm).map(|value| (algorithm, value)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// `other` に値がある項目はそちらを優先して上書きする。
+    pub fn overlay(self, other: Checksums) -> Checksums {
+        Checksums {
+            sha512: other.sha512.or(self.sha512),
+            sha256: other.sha256.or(self.sha256),
+            sha1: other.sha1.or(self.sha1),
+        }
+    }
+
+    /// 検証に使うチェックサム。sha1 は `allow_weak` の場合だけ使う。
+    pub fn strongest(&self, allow_weak: bool) -> Option<Checksum> {
+        self.iter()
+            .find(|(algorithm, _)| allow_weak || !algorithm.is_weak())
+            .map(|(algorithm, value)| Checksum {
+                algorithm,
+                value: value.to_ascii_lowercase(),
+            })
+    }
+}
+
+/// 検証に使う 1 つのチェックサム。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    /// 小文字の16進数。
+    pub value: String,
+}
+
+impl Checksum {
+    pub fn verify(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let actual = self.algorithm.digest_file(path)?;
+        if actual != self.value {
+            return Err(format!(
+                "チェックサム不一致 ({}): expected={}, actual={}",
+                self.algorithm, self.value, actual
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.algorithm, self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strongest_prefers_sha512_and_requires_opt_in_for_sha1() {
+        let checksums = Checksums {
+            sha512: None,
+            sha256: Some("AB".repeat(32)),
+            sha1: Some("cd".repeat(20)),
+        };
+        let checksum = checksums.strongest(false).expect("checksum");
+        assert_eq!(checksum.algorithm, Algorithm::Sha256);
+        assert_eq!(checksum.value, "ab".repeat(32));
+
+        let weak_only = Checksums {
+            sha1: Some("cd".repeat(20)),
+            ..Checksums::default()
+        };
+        assert_eq!(weak_only.strongest(false), None);
+        assert_eq!(
+            weak_only.strongest(true).map(|checksum| checksum.algorithm),
+            Some(Algorithm::Sha1)
+        );
+    }
+
+    #[test]
+    fn verify_uses_the_selected_algorithm() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let path = temp_dir.path().join("velocity.jar");
+        std::fs::write(&path, b"velocity-jar").expect("write jar");
+
+        for algorithm in [Algorithm::Sha512, Algorithm::Sha256, Algorithm::Sha1] {
+            let value = algorithm.digest_file(&path).expect("digest");
+            assert!(algorithm.is_valid_hex(&value));
+            let checksum = Checksum { algorithm, value };
+            checksum.verify(&path).expect("verify");
+        }
+
+        let message = Checksum {
+            algorithm: Algorithm::Sha512,
+            value: "0".repeat(128),
+        }
+        .verify(&path)
+        .expect_err("mismatch")
+        .to_string();
+        assert!(message.contains("チェックサム不一致 (sha512)"));
+    }
+}
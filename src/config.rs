@@ -0,0 +1,508 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml_edit::{DocumentMut, TableLike};
+
+use crate::manifest::ManifestCheck;
+
+const CONFIG_DIR_NAME: &str = "mc-velocity-installer";
+
+/// 設定ファイル（TOML）の内容。環境変数・フラグの値で上書きして使う。
+#[derive(Debug, Default, Clone)]
+pub struct Config {
+    pub http: HttpConfig,
+    pub mirrors: MirrorConfig,
+    pub cache: CacheConfig,
+    pub index: IndexConfig,
+    pub checksum: ChecksumConfig,
+    pub signature: SignatureConfig,
+    pub jar: JarConfig,
+}
+
+/// `[http]` テーブル。未指定の項目は既定値を使う。
+#[derive(Debug, Default, Clone)]
+pub struct HttpConfig {
+    /// 接続のタイムアウト（秒）。
+    pub connect_timeout: Option<u64>,
+    /// 接続・読み込み・書き込みそれぞれのタイムアウト（秒）。
+    pub timeout: Option<u64>,
+    pub proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    /// 追加で信頼する CA 証明書（PEM、複数可）。
+    pub ca_bundle: Option<PathBuf>,
+    pub user_agent: Option<String>,
+}
+
+/// `[mirrors]` テーブル。本来の取得元が失敗した場合に、記載順に試す。
+#[derive(Debug, Default, Clone)]
+pub struct MirrorConfig {
+    /// バージョン一覧の URL（またはローカルのパス）。
+    pub index: Option<Vec<String>>,
+    /// jar を置いたディレクトリの URL（またはローカルのパス）。jar 名を付けて取得する。
+    pub jar: Option<Vec<String>>,
+}
+
+/// `[cache]` テーブル。取得した jar のキャッシュ。
+#[derive(Debug, Default, Clone)]
+pub struct CacheConfig {
+    pub dir: Option<PathBuf>,
+    /// `false` の場合はキャッシュを使わない（既定は使う）。
+    pub enabled: Option<bool>,
+}
+
+/// `[index]` テーブル。バージョン一覧の扱い。
+#[derive(Debug, Default, Clone)]
+pub struct IndexConfig {
+    /// 不正なエントリを除外して続行する（既定はエラーにする）。
+    pub skip_invalid_entries: Option<bool>,
+}
+
+/// `[checksum]` テーブル。jar の検証に使うチェックサム。
+#[derive(Debug, Default, Clone)]
+pub struct ChecksumConfig {
+    /// sha1 しか無い jar を sha1 で検証することを許可する（既定は許可しない）。
+    pub allow_weak: Option<bool>,
+}
+
+/// `[signature]` テーブル。バージョン一覧と jar の署名検証。
+#[derive(Debug, Default, Clone)]
+pub struct SignatureConfig {
+    /// 信頼する ed25519 公開鍵（32 バイトの Base64）。
+    pub keys: Option<Vec<String>>,
+    /// 署名の無いもの・検証できないものを一切使わない（既定は警告して続行）。
+    pub required: Option<bool>,
+}
+
+/// `[jar]` テーブル。取得した jar の中身の確認と、古い jar の保持数。
+#[derive(Debug, Default, Clone)]
+pub struct JarConfig {
+    /// `MANIFEST.MF` が選んだバージョンと食い違う場合の扱い（既定は警告）。
+    pub manifest_check: Option<ManifestCheck>,
+    /// 起動スクリプトが参照する jar のほかに残す古い jar の数。未指定なら削除しない。
+    pub keep: Option<usize>,
+}
+
+impl Config {
+    /// `path` が無い場合は空の設定を返す。
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("設定ファイルを読み込めません: {} ({err})", path.display()))?;
+        Self::parse(&contents)
+            .map_err(|err| format!("設定ファイルが不正です: {} ({err})", path.display()).into())
+    }
+
+    fn parse(contents: &str) -> Result<Self, Box<dyn Error>> {
+        let document: DocumentMut = contents.parse()?;
+        let mut config = Self::default();
+        if let Some(http) = table(&document, "http")? {
+            config.http = HttpConfig {
+                connect_timeout: get_seconds(http, "http", "connect_timeout")?,
+                timeout: get_seconds(http, "http", "timeout")?,
+                proxy: get_string(http, "http", "proxy")?,
+                no_proxy: get_string(http, "http", "no_proxy")?,
+                ca_bundle: get_string(http, "http", "ca_bundle")?.map(PathBuf::from),
+                user_agent: get_string(http, "http", "user_agent")?,
+            };
+        }
+        if let Some(mirrors) = table(&document, "mirrors")? {
+            config.mirrors = MirrorConfig {
+                index: get_string_list(mirrors, "mirrors", "index")?,
+                jar: get_string_list(mirrors, "mirrors", "jar")?,
+            };
+        }
+        if let Some(cache) = table(&document, "cache")? {
+            config.cache = CacheConfig {
+                dir: get_string(cache, "cache", "dir")?.map(PathBuf::from),
+                enabled: get_bool(cache, "cache", "enabled")?,
+            };
+        }
+        if let Some(index) = table(&document, "index")? {
+            config.index = IndexConfig {
+                skip_invalid_entries: get_bool(index, "index", "skip_invalid_entries")?,
+            };
+        }
+        if let Some(checksum) = table(&document, "checksum")? {
+            config.checksum = ChecksumConfig {
+                allow_weak: get_bool(checksum, "checksum", "allow_weak")?,
+            };
+        }
+        if let Some(signature) = table(&document, "signature")? {
+            config.signature = SignatureConfig {
+                keys: get_string_list(signature, "signature", "keys")?,
+                required: get_bool(signature, "signature", "required")?,
+            };
+        }
+        if let Some(jar) = table(&document, "jar")? {
+            config.jar = JarConfig {
+                manifest_check: get_string(jar, "jar", "manifest_check")?
+                    .map(|value| ManifestCheck::parse(&value))
+                    .transpose()
+                    .map_err(|err| format!("jar.manifest_check: {err}"))?,
+                keep: get_count(jar, "jar", "keep")?,
+            };
+        }
+        Ok(config)
+    }
+
+    /// `$XDG_CONFIG_HOME/mc-velocity-installer/config.toml`（未設定なら `~/.config` 配下）。
+    pub fn default_path() -> Option<PathBuf> {
+        config_home().map(|dir| dir.join(CONFIG_DIR_NAME).join("config.toml"))
+    }
+}
+
+impl HttpConfig {
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            connect_timeout: env_seconds("MC_VELOCITY_CONNECT_TIMEOUT")?,
+            timeout: env_seconds("MC_VELOCITY_TIMEOUT")?,
+            proxy: env_string("MC_VELOCITY_PROXY"),
+            no_proxy: env_string("MC_VELOCITY_NO_PROXY"),
+            ca_bundle: env_string("MC_VELOCITY_CA_BUNDLE").map(PathBuf::from),
+            user_agent: env_string("MC_VELOCITY_USER_AGENT"),
+        })
+    }
+
+    /// `other` に値がある項目はそちらを優先して上書きする。
+    pub fn overlay(self, other: HttpConfig) -> HttpConfig {
+        HttpConfig {
+            connect_timeout: other.connect_timeout.or(self.connect_timeout),
+            timeout: other.timeout.or(self.timeout),
+            proxy: other.proxy.or(self.proxy),
+            no_proxy: other.no_proxy.or(self.no_proxy),
+            ca_bundle: other.ca_bundle.or(self.ca_bundle),
+            user_agent: other.user_agent.or(self.user_agent),
+        }
+    }
+}
+
+impl MirrorConfig {
+    /// カンマ区切りの `MC_VELOCITY_INDEX_MIRRORS` / `MC_VELOCITY_JAR_MIRRORS`。
+    pub fn from_env() -> Self {
+        Self {
+            index: env_list("MC_VELOCITY_INDEX_MIRRORS"),
+            jar: env_list("MC_VELOCITY_JAR_MIRRORS"),
+        }
+    }
+
+    /// `other` に値がある項目はそちらのリストで置き換える。
+    pub fn overlay(self, other: MirrorConfig) -> MirrorConfig {
+        MirrorConfig {
+            index: other.index.or(self.index),
+            jar: other.jar.or(self.jar),
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn from_env() -> Self {
+        Self {
+            dir: env_string("MC_VELOCITY_CACHE_DIR").map(PathBuf::from),
+            enabled: env_string("MC_VELOCITY_NO_CACHE").map(|value| value == "0"),
+        }
+    }
+
+    /// `other` に値がある項目はそちらを優先して上書きする。
+    pub fn overlay(self, other: CacheConfig) -> CacheConfig {
+        CacheConfig {
+            dir: other.dir.or(self.dir),
+            enabled: other.enabled.or(self.enabled),
+        }
+    }
+}
+
+impl IndexConfig {
+    pub fn from_env() -> Self {
+        Self {
+            skip_invalid_entries: env_string("MC_VELOCITY_SKIP_INVALID_ENTRIES")
+                .map(|value| value != "0"),
+        }
+    }
+
+    /// `other` に値がある項目はそちらを優先して上書きする。
+    pub fn overlay(self, other: IndexConfig) -> IndexConfig {
+        IndexConfig {
+            skip_invalid_entries: other.skip_invalid_entries.or(self.skip_invalid_entries),
+        }
+    }
+}
+
+impl ChecksumConfig {
+    pub fn from_env() -> Self {
+        Self {
+            allow_weak: env_string("MC_VELOCITY_ALLOW_WEAK_CHECKSUM").map(|value| value != "0"),
+        }
+    }
+
+    /// `other` に値がある項目はそちらを優先して上書きする。
+    pub fn overlay(self, other: ChecksumConfig) -> ChecksumConfig {
+        ChecksumConfig {
+            allow_weak: other.allow_weak.or(self.allow_weak),
+        }
+    }
+}
+
+impl SignatureConfig {
+    /// カンマ区切りの `MC_VELOCITY_SIGNATURE_KEYS` と `MC_VELOCITY_REQUIRE_SIGNATURE`。
+    pub fn from_env() -> Self {
+        Self {
+            keys: env_list("MC_VELOCITY_SIGNATURE_KEYS"),
+            required: env_string("MC_VELOCITY_REQUIRE_SIGNATURE").map(|value| value != "0"),
+        }
+    }
+
+    /// `other` に値がある項目はそちらを優先して上書きする（鍵はリストごと置き換える）。
+    pub fn overlay(self, other: SignatureConfig) -> SignatureConfig {
+        SignatureConfig {
+            keys: other.keys.or(self.keys),
+            required: other.required.or(self.required),
+        }
+    }
+}
+
+impl JarConfig {
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            manifest_check: env_string("MC_VELOCITY_JAR_MANIFEST_CHECK")
+                .map(|value| ManifestCheck::parse(&value))
+                .transpose()?,
+            keep: env_string("MC_VELOCITY_JAR_KEEP")
+                .map(|value| parse_count("MC_VELOCITY_JAR_KEEP", &value))
+                .transpose()?,
+        })
+    }
+
+    /// `other` に値がある項目はそちらを優先して上書きする。
+    pub fn overlay(self, other: JarConfig) -> JarConfig {
+        JarConfig {
+            manifest_check: other.manifest_check.or(self.manifest_check),
+            keep: other.keep.or(self.keep),
+        }
+    }
+}
+
+pub fn parse_seconds(name: &str, value: &str) -> Result<u64, Box<dyn Error>> {
+    value
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| format!("{name} には秒数（整数）を指定してください: {value}").into())
+}
+
+pub fn parse_count(name: &str, value: &str) -> Result<usize, Box<dyn Error>> {
+    value
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| format!("{name} には個数（0 以上の整数）を指定してください: {value}").into())
+}
+
+fn config_home() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(home) = std::env::var_os("HOME").filter(|home| !home.is_empty()) {
+        return Some(PathBuf::from(home).join(".config"));
+    }
+    std::env::var_os("APPDATA").map(PathBuf::from)
+}
+
+fn table<'a>(
+    document: &'a DocumentMut,
+    name: &str,
+) -> Result<Option<&'a dyn TableLike>, Box<dyn Error>> {
+    match document.get(name) {
+        Some(item) => item
+            .as_table_like()
+            .map(Some)
+            .ok_or_else(|| format!("{name} はテーブルで指定してください").into()),
+        None => Ok(None),
+    }
+}
+
+fn get_string(
+    table: &dyn TableLike,
+    section: &str,
+    key: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+    match table.get(key) {
+        Some(item) => item
+            .as_str()
+            .map(|value| Some(value.to_string()))
+            .ok_or_else(|| format!("{section}.{key} は文字列で指定してください").into()),
+        None => Ok(None),
+    }
+}
+
+fn get_seconds(
+    table: &dyn TableLike,
+    section: &str,
+    key: &str,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    match table.get(key) {
+        Some(item) => item
+            .as_integer()
+            .and_then(|value| u64::try_from(value).ok())
+            .map(Some)
+            .ok_or_else(|| format!("{section}.{key} は秒数（整数）で指定してください").into()),
+        None => Ok(None),
+    }
+}
+
+fn get_count(
+    table: &dyn TableLike,
+    section: &str,
+    key: &str,
+) -> Result<Option<usize>, Box<dyn Error>> {
+    match table.get(key) {
+        Some(item) => item
+            .as_integer()
+            .and_then(|value| usize::try_from(value).ok())
+            .map(Some)
+            .ok_or_else(|| {
+                format!("{section}.{key} は個数（0 以上の整数）で指定してください").into()
+            }),
+        None => Ok(None),
+    }
+}
+
+fn get_bool(
+    table: &dyn TableLike,
+    section: &str,
+    key: &str,
+) -> Result<Option<bool>, Box<dyn Error>> {
+    match table.get(key) {
+        Some(item) => item
+            .as_bool()
+            .map(Some)
+            .ok_or_else(|| format!("{section}.{key} は true / false で指定してください").into()),
+        None => Ok(None),
+    }
+}
+
+fn get_string_list(
+    table: &dyn TableLike,
+    section: &str,
+    key: &str,
+) -> Result<Option<Vec<String>>, Box<dyn Error>> {
+    let Some(item) = table.get(key) else {
+        return Ok(None);
+    };
+    let error = || format!("{section}.{key} は文字列の配列で指定してください");
+    let array = item.as_array().ok_or_else(error)?;
+    let mut values = Vec::new();
+    for value in array.iter() {
+        values.push(value.as_str().ok_or_else(error)?.to_string());
+    }
+    Ok(Some(values))
+}
+
+fn env_string(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn env_list(name: &str) -> Option<Vec<String>> {
+    env_string(name).map(|value| {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+fn env_seconds(name: &str) -> Result<Option<u64>, Box<dyn Error>> {
+    env_string(name)
+        .map(|value| parse_seconds(name, &value))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_all_tables() {
+        let config = Config::parse(
+            r#"
+[http]
+connect_timeout = 5
+timeout = 60
+proxy = "http://proxy.internal:3128"
+no_proxy = "localhost,.internal"
+ca_bundle = "/etc/ssl/corp.pem"
+user_agent = "custom-agent"
+
+[mirrors]
+index = ["https://mirror-a.example.com/velocity.json", "/srv/mirror/velocity.json"]
+jar = ["https://mirror-a.example.com/jars/"]
+
+[cache]
+dir = "/var/cache/velocity-jars"
+enabled = false
+
+[index]
+skip_invalid_entries = true
+
+[checksum]
+allow_weak = true
+
+[signature]
+keys = ["MCowBQYDK2VwAyEA"]
+required = true
+
+[jar]
+manifest_check = "fail"
+keep = 2
+"#,
+        )
+        .expect("parse");
+        assert_eq!(config.http.connect_timeout, Some(5));
+        assert_eq!(config.http.timeout, Some(60));
+        assert_eq!(
+            config.http.proxy.as_deref(),
+            Some("http://proxy.internal:3128")
+        );
+        assert_eq!(config.http.no_proxy.as_deref(), Some("localhost,.internal"));
+        assert_eq!(
+            config.http.ca_bundle,
+            Some(PathBuf::from("/etc/ssl/corp.pem"))
+        );
+        assert_eq!(config.http.user_agent.as_deref(), Some("custom-agent"));
+        assert_eq!(
+            config.mirrors.index,
+            Some(vec![
+                "https://mirror-a.example.com/velocity.json".to_string(),
+                "/srv/mirror/velocity.json".to_string(),
+            ])
+        );
+        assert_eq!(
+            config.mirrors.jar,
+            Some(vec!["https://mirror-a.example.com/jars/".to_string()])
+        );
+        assert_eq!(
+            config.cache.dir,
+            Some(PathBuf::from("/var/cache/velocity-jars"))
+        );
+        assert_eq!(config.cache.enabled, Some(false));
+        assert_eq!(config.index.skip_invalid_entries, Some(true));
+        assert_eq!(config.checksum.allow_weak, Some(true));
+        assert_eq!(
+            config.signature.keys,
+            Some(vec!["MCowBQYDK2VwAyEA".to_string()])
+        );
+        assert_eq!(config.signature.required, Some(true));
+        assert_eq!(config.jar.manifest_check, Some(ManifestCheck::Fail));
+        assert_eq!(config.jar.keep, Some(2));
+    }
+
+    #[test]
+    fn parse_rejects_wrong_types() {
+        let message = Config::parse("[http]\ntimeout = \"slow\"\n")
+            .expect_err("error")
+            .to_string();
+        assert!(message.contains("http.timeout"));
+    }
+}
@@ -0,0 +1,749 @@
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::{StatusCode, Url, blocking::Client};
+
+use crate::cache::JarCache;
+use crate::checksum::Checksum;
+use crate::location;
+use crate::manifest::{self, ManifestCheck};
+use crate::progress::{Progress, ProgressStyle};
+use crate::signature::SignatureVerifier;
+use crate::version::VersionInfo;
+
+/// 一時的な失敗に対する再試行の設定。
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// `attempt` 回目の失敗後に待つ時間（指数バックオフ）。
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+/// 再試行すべきかどうかで分類した失敗。
+enum Failure {
+    Transient(Box<dyn Error>),
+    Fatal(Box<dyn Error>),
+}
+
+/// jar の取得に使う HTTP クライアントと設定。
+pub struct Downloader {
+    client: Client,
+    progress: ProgressStyle,
+    retry: RetryPolicy,
+    /// 本来の URL が失敗した場合に試す、jar を置いたディレクトリ。
+    mirrors: Vec<String>,
+    cache: Option<JarCache>,
+    /// sha1 しか無い jar を sha1 で検証することを許可する。
+    allow_weak_checksum: bool,
+    /// jar を `<url>.sig` の署名で検証する。
+    signature: Option<SignatureVerifier>,
+    /// 配置前に jar の `MANIFEST.MF` を確かめる。
+    manifest_check: ManifestCheck,
+}
+
+impl Downloader {
+    pub fn new(client: Client, progress: ProgressStyle) -> Self {
+        Self {
+            client,
+            progress,
+            retry: RetryPolicy::default(),
+            mirrors: Vec::new(),
+            cache: None,
+            allow_weak_checksum: false,
+            signature: None,
+            manifest_check: ManifestCheck::Warn,
+        }
+    }
+
+    pub fn with_mirrors(mut self, mirrors: Vec<String>) -> Self {
+        self.mirrors = mirrors;
+        self
+    }
+
+    pub fn with_cache(mut self, cache: Option<JarCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_weak_checksum(mut self, allow: bool) -> Self {
+        self.allow_weak_checksum = allow;
+        self
+    }
+
+    pub fn with_signature(mut self, signature: Option<SignatureVerifier>) -> Self {
+        self.signature = signature;
+        self
+    }
+
+    pub fn with_manifest_check(mut self, check: ManifestCheck) -> Self {
+        self.manifest_check = check;
+        self
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// `version` の検証に使うチェックサム（sha512 > sha256 > 許可されていれば sha1）。
+    pub fn checksum_for(&self, version: &VersionInfo) -> Result<Checksum, Box<dyn Error>> {
+        if let Some(checksum) = version.checksums.strongest(self.allow_weak_checksum) {
+            return Ok(checksum);
+        }
+        if version.checksums.sha1.is_some() {
+            return Err(format!(
+                "{} には sha1 のチェックサムしかありません。sha1 で検証するには --allow-weak-checksum を指定してください。",
+                version.version
+            )
+            .into());
+        }
+        Err(format!("{} にチェックサムがありません。", version.version).into())
+    }
+
+    /// jar を取得してチェックサムを検証し、一致した場合のみ `dest_path` に配置する。
+    ///
+    /// 取得中は `<jar>.part` に書き込み、中断された場合は次回 Range リクエストで続きから再開する。
+    /// キャッシュに同じチェックサムの jar があれば、取得せずにそれを使う。
+    /// 署名を検証する場合は、チェックサムに続けて署名も一致した場合のみ配置する。
+    /// 配置する前に `MANIFEST.MF` が選んだバージョンのものかを確かめる。
+    pub fn download_verified(
+        &self,
+        version: &VersionInfo,
+        dest_path: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        download_verified(self, version, dest_path)
+    }
+}
+
+fn download_verified(
+    downloader: &Downloader,
+    version: &VersionInfo,
+    dest_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let checksum = downloader.checksum_for(version)?;
+    // sha1 は衝突させた jar を作れるため、キャッシュの鍵には使わない。
+    let cache = downloader
+        .cache
+        .as_ref()
+        .filter(|_| !checksum.algorithm.is_weak());
+    let part_path = part_path(dest_path)?;
+    if let Some(cache) = cache
+        && let Some(cached) = cache.lookup(&checksum)
+        && cached_signature_is_valid(downloader, cache, version, &cached)
+    {
+        downloader.progress.status(&format!(
+            "キャッシュ済みの jar を使います: {}",
+            cached.display()
+        ));
+        copy_local(&cached, &part_path)?;
+        return install_part(downloader, version, &part_path, dest_path);
+    }
+    let urls = candidate_urls(downloader, version)?;
+    let mut failures = Vec::new();
+    for url in &urls {
+        let fetched = fetch_verified(downloader, url, &checksum, &part_path)
+            .and_then(|()| check_signature(downloader, url, &part_path));
+        match fetched {
+            Ok(signature) => {
+                if urls.len() > 1 {
+                    downloader.progress.status(&format!("jar の取得元: {url}"));
+                }
+                // 中身の確認に通らなかった jar はキャッシュに残さない
+                check_part(downloader, version, &part_path)?;
+                if let Some(cache) = cache
+                    && location::local_path(url).is_none()
+                {
+                    store_in_cache(
+                        cache,
+                        &checksum,
+                        signature.as_deref(),
+                        dest_path,
+                        &part_path,
+                    );
+                }
+                fs::rename(&part_path, dest_path)?;
+                return Ok(());
+            }
+            Err(err) if urls.len() == 1 => return Err(err),
+            Err(err) => {
+                eprintln!("警告: {url} から取得できませんでした（{err}）。");
+                failures.push(format!("{url} ({err})"));
+            }
+        }
+    }
+    Err(format!(
+        "すべての取得元で jar の取得に失敗しました: {}",
+        failures.join(", ")
+    )
+    .into())
+}
+
+/// 検証済みの `.part` の中身を確かめてから jar 名にする。確認に失敗した場合は `.part` を削除する。
+fn install_part(
+    downloader: &Downloader,
+    version: &VersionInfo,
+    part_path: &Path,
+    dest_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    check_part(downloader, version, part_path)?;
+    fs::rename(part_path, dest_path)?;
+    Ok(())
+}
+
+/// `.part` の `MANIFEST.MF` が選んだバージョンのものかを確かめる。確認に失敗した場合は `.part` を削除する。
+fn check_part(
+    downloader: &Downloader,
+    version: &VersionInfo,
+    part_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    if let Err(err) = manifest::check_jar(
+        part_path,
+        version,
+        downloader.manifest_check,
+        downloader.progress,
+    ) {
+        let _ = fs::remove_file(part_path);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// 取得した jar を署名で検証し、検証できた署名を返す。一致しない場合は `.part` を削除する。
+fn check_signature(
+    downloader: &Downloader,
+    url: &str,
+    part_path: &Path,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let Some(verifier) = &downloader.signature else {
+        return Ok(None);
+    };
+    let result = verifier.check(&downloader.client, url, &fs::read(part_path)?);
+    if result.is_err() {
+        let _ = fs::remove_file(part_path);
+    }
+    result
+}
+
+/// キャッシュ済みの jar を使ってよいか。署名を検証する場合は、保存時の署名で検証し直す。
+///
+/// 署名が保存されていない jar（署名を検証せずに保存したもの）は使わずに取得し直す。
+fn cached_signature_is_valid(
+    downloader: &Downloader,
+    cache: &JarCache,
+    version: &VersionInfo,
+    cached: &Path,
+) -> bool {
+    let Some(verifier) = &downloader.signature else {
+        return true;
+    };
+    let (Some(signature), Ok(data)) = (cache.signature(cached), fs::read(cached)) else {
+        return false;
+    };
+    verifier.verify(&version.url, &data, &signature).is_ok()
+}
+
+/// 検証済みの jar をキャッシュに保存する。失敗しても取得自体は成功として扱う。
+fn store_in_cache(
+    cache: &JarCache,
+    checksum: &Checksum,
+    signature: Option<&str>,
+    dest_path: &Path,
+    part_path: &Path,
+) {
+    let Some(jar_name) = dest_path.file_name() else {
+        return;
+    };
+    if let Err(err) = cache.store(checksum, &jar_name.to_string_lossy(), part_path, signature) {
+        eprintln!("警告: jar をキャッシュに保存できませんでした（{err}）。");
+    }
+}
+
+/// 本来の URL に続けて、ミラーの URL を優先順に並べる。
+///
+/// ローカルの jar（`--jar` や `file://`）はミラーを使わない。
+fn candidate_urls(
+    downloader: &Downloader,
+    version: &VersionInfo,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut urls = vec![version.url.clone()];
+    if location::local_path(&version.url).is_some() {
+        return Ok(urls);
+    }
+    let jar_name = jar_filename_from_url(&version.url, &version.version);
+    for mirror in &downloader.mirrors {
+        let mut base = location::location_url(mirror)
+            .map_err(|err| format!("ミラーの URL が不正です: {mirror} ({err})"))?;
+        if !base.path().ends_with('/') {
+            let path = format!("{}/", base.path());
+            base.set_path(&path);
+        }
+        urls.push(base.join(&jar_name)?.to_string());
+    }
+    Ok(urls)
+}
+
+/// `url` から `part_path` に取得し、チェックサムが一致することを確かめる。
+fn fetch_verified(
+    downloader: &Downloader,
+    url: &str,
+    checksum: &Checksum,
+    part_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let resumed = match location::local_path(url) {
+        Some(path) => {
+            downloader
+                .progress
+                .status(&format!("コピー中: {}", path.display()));
+            copy_local(&path, part_path)?;
+            false
+        }
+        None => {
+            downloader
+                .progress
+                .status(&format!("ダウンロード中: {url}"));
+            let resumed = part_path.exists();
+            if resumed {
+                downloader.progress.status(&format!(
+                    "前回の途中までのファイルから再開します: {}",
+                    part_path.display()
+                ));
+            }
+            fetch_with_retry(downloader, url, part_path)?;
+            resumed
+        }
+    };
+
+    let mut result = checksum.verify(part_path);
+    if result.is_err() && resumed {
+        // 再開元のファイルが別物だった可能性があるため、最初から取り直す。
+        downloader
+            .progress
+            .status("チェックサムが一致しないため、最初からダウンロードし直します。");
+        fs::remove_file(part_path)?;
+        fetch_with_retry(downloader, url, part_path)?;
+        result = checksum.verify(part_path);
+    }
+    if result.is_err() {
+        let _ = fs::remove_file(part_path);
+    }
+    result
+}
+
+fn part_path(dest_path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let name = dest_path
+        .file_name()
+        .ok_or_else(|| format!("保存先のファイル名がありません: {}", dest_path.display()))?;
+    let mut part_name = name.to_os_string();
+    part_name.push(".part");
+    Ok(dest_path.with_file_name(part_name))
+}
+
+fn copy_local(src: &Path, part_path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut source =
+        File::open(src).map_err(|err| format!("jar を開けません: {} ({err})", src.display()))?;
+    let mut file = File::create(part_path)?;
+    std::io::copy(&mut source, &mut file)?;
+    Ok(())
+}
+
+fn fetch_with_retry(
+    downloader: &Downloader,
+    url: &str,
+    part_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let policy = &downloader.retry;
+    let mut attempt = 1;
+    loop {
+        match fetch_once(downloader, url, part_path) {
+            Ok(()) => return Ok(()),
+            Err(Failure::Fatal(err)) => return Err(err),
+            Err(Failure::Transient(err)) if attempt < policy.max_attempts => {
+                let delay = policy.delay(attempt);
+                eprintln!(
+                    "警告: ダウンロードに失敗しました（{err}）。{:.1} 秒後に再試行します ({}/{})",
+                    delay.as_secs_f64(),
+                    attempt,
+                    policy.max_attempts
+                );
+                thread::sleep(delay);
+                attempt += 1;
+            }
+            Err(Failure::Transient(err)) => {
+                return Err(
+                    format!("ダウンロードに失敗しました（{attempt} 回試行）: {err}").into(),
+                );
+            }
+        }
+    }
+}
+
+/// 1 回分のリクエスト。`part_path` に既存のデータがあれば続きから取得する。
+fn fetch_once(downloader: &Downloader, url: &str, part_path: &Path) -> Result<(), Failure> {
+    let offset = fs::metadata(part_path).map(|meta| meta.len()).unwrap_or(0);
+    let mut request = downloader.client.get(url);
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={offset}-"));
+    }
+    let mut response = request.send().map_err(|err| {
+        if err.is_builder() {
+            Failure::Fatal(err.into())
+        } else {
+            Failure::Transient(err.into())
+        }
+    })?;
+
+    let status = response.status();
+    let mut start = 0;
+    let mut file = match status {
+        StatusCode::PARTIAL_CONTENT if offset > 0 => {
+            if !content_range_starts_at(&response, offset) {
+                fs::remove_file(part_path).map_err(|err| Failure::Fatal(err.into()))?;
+                return Err(Failure::Transient(
+                    "サーバーが想定外の範囲を返しました".into(),
+                ));
+            }
+            start = offset;
+            OpenOptions::new().append(true).open(part_path)
+        }
+        StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+            if content_range_total(&response) == Some(offset) {
+                return Ok(());
+            }
+            fs::remove_file(part_path).map_err(|err| Failure::Fatal(err.into()))?;
+            return Err(Failure::Transient(
+                "途中までのファイルが利用できません".into(),
+            ));
+        }
+        status if status.is_success() => File::create(part_path),
+        status if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS => {
+            return Err(Failure::Transient(format!("HTTP {status}").into()));
+        }
+        status => return Err(Failure::Fatal(format!("HTTP {status}: {url}").into())),
+    }
+    .map_err(|err| Failure::Fatal(err.into()))?;
+
+    let total = response.content_length().map(|length| start + length);
+    let mut progress = Progress::new(downloader.progress, start, total);
+    let mut buffer = [0u8; 8192];
+    loop {
+        let bytes_read = match response.read(&mut buffer) {
+            Ok(bytes_read) => bytes_read,
+            Err(err) => {
+                progress.finish();
+                return Err(Failure::Transient(err.into()));
+            }
+        };
+        if bytes_read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..bytes_read])
+            .map_err(|err| Failure::Fatal(err.into()))?;
+        progress.advance(bytes_read as u64);
+    }
+    progress.finish();
+    Ok(())
+}
+
+/// `Content-Range: bytes <start>-<end>/<total>` の start が `offset` と一致するか。
+fn content_range_starts_at(response: &reqwest::blocking::Response, offset: u64) -> bool {
+    response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("bytes "))
+        .and_then(|range| range.split_once('-'))
+        .and_then(|(start, _)| start.parse::<u64>().ok())
+        == Some(offset)
+}
+
+/// `Content-Range: bytes */<total>` の total。
+fn content_range_total(response: &reqwest::blocking::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .rsplit_once('/')?
+        .1
+        .parse()
+        .ok()
+}
+
+pub fn jar_filename_from_url(url: &str, version: &str) -> String {
+    if let Ok(parsed) = Url::parse(url)
+        && let Some(name) = parsed
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+        && !name.is_empty()
+    {
+        return name.to_string();
+    }
+    format!("velocity-{}.jar", version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::Checksums;
+    use httpmock::Method::GET;
+    use httpmock::MockServer;
+    use sha2::{Digest, Sha256};
+    use std::io::BufRead;
+    use std::net::TcpListener;
+
+    const JAR_BYTES: &[u8] = b"0123456789velocity";
+
+    fn version_for(url: String) -> VersionInfo {
+        VersionInfo {
+            version: "1.0.0".to_string(),
+            kind: "stable".to_string(),
+            url,
+            checksums: Checksums::sha256(&format!("{:x}", Sha256::digest(JAR_BYTES))),
+            build: None,
+        }
+    }
+
+    fn test_downloader() -> Downloader {
+        Downloader {
+            client: Client::builder().build().expect("client"),
+            progress: ProgressStyle::Quiet,
+            retry: RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(10),
+                max_delay: Duration::from_millis(10),
+            },
+            mirrors: Vec::new(),
+            cache: None,
+            allow_weak_checksum: false,
+            signature: None,
+            manifest_check: ManifestCheck::Off,
+        }
+    }
+
+    #[test]
+    fn resumes_existing_part_file_with_range_request() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let dest = temp_dir.path().join("velocity.jar");
+        fs::write(temp_dir.path().join("velocity.jar.part"), &JAR_BYTES[..10]).expect("part");
+
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/velocity.jar")
+                .header("range", "bytes=10-");
+            then.status(206)
+                .header("content-range", format!("bytes 10-17/{}", JAR_BYTES.len()))
+                .body(&JAR_BYTES[10..]);
+        });
+
+        let version = version_for(server.url("/velocity.jar"));
+        test_downloader()
+            .download_verified(&version, &dest)
+            .expect("download");
+
+        mock.assert();
+        assert_eq!(fs::read(&dest).expect("read jar"), JAR_BYTES);
+        assert!(!temp_dir.path().join("velocity.jar.part").exists());
+    }
+
+    #[test]
+    fn checksum_mismatch_keeps_existing_jar() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let dest = temp_dir.path().join("velocity.jar");
+        fs::write(&dest, b"old jar").expect("old jar");
+
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/velocity.jar");
+            then.status(200).body(b"tampered");
+        });
+
+        let version = version_for(server.url("/velocity.jar"));
+        let result = test_downloader().download_verified(&version, &dest);
+
+        let message = result.expect_err("error").to_string();
+        assert!(message.contains("チェックサム不一致"));
+        assert_eq!(fs::read(&dest).expect("read jar"), b"old jar");
+        assert!(!temp_dir.path().join("velocity.jar.part").exists());
+    }
+
+    #[test]
+    fn does_not_retry_client_errors() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/velocity.jar");
+            then.status(404);
+        });
+
+        let version = version_for(server.url("/velocity.jar"));
+        let dest = temp_dir.path().join("velocity.jar");
+        let result = test_downloader().download_verified(&version, &dest);
+
+        assert!(result.is_err());
+        mock.assert_hits(1);
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn falls_back_to_mirrors_in_order() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let dest = temp_dir.path().join("velocity.jar");
+
+        let origin = MockServer::start();
+        origin.mock(|when, then| {
+            when.method(GET).path("/velocity.jar");
+            then.status(404);
+        });
+        let tampered = MockServer::start();
+        let tampered_mock = tampered.mock(|when, then| {
+            when.method(GET).path("/jars/velocity.jar");
+            then.status(200).body(b"tampered");
+        });
+        let mirror = MockServer::start();
+        let mirror_mock = mirror.mock(|when, then| {
+            when.method(GET).path("/jars/velocity.jar");
+            then.status(200).body(JAR_BYTES);
+        });
+
+        let version = version_for(origin.url("/velocity.jar"));
+        test_downloader()
+            .with_mirrors(vec![tampered.url("/jars"), mirror.url("/jars/")])
+            .download_verified(&version, &dest)
+            .expect("download");
+
+        tampered_mock.assert();
+        mirror_mock.assert();
+        assert_eq!(fs::read(&dest).expect("read jar"), JAR_BYTES);
+        assert!(!temp_dir.path().join("velocity.jar.part").exists());
+    }
+
+    #[test]
+    fn reuses_cached_jar_without_downloading() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let cache = JarCache::new(temp_dir.path().join("cache"));
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/velocity.jar");
+            then.status(200).body(JAR_BYTES);
+        });
+        let version = version_for(server.url("/velocity.jar"));
+        let downloader = test_downloader().with_cache(Some(cache.clone()));
+
+        for instance in ["a", "b"] {
+            let dir = temp_dir.path().join(instance);
+            fs::create_dir_all(&dir).expect("instance dir");
+            downloader
+                .download_verified(&version, &dir.join("velocity.jar"))
+                .expect("download");
+            assert_eq!(
+                fs::read(dir.join("velocity.jar")).expect("read jar"),
+                JAR_BYTES
+            );
+        }
+
+        mock.assert_hits(1);
+        assert_eq!(cache.entries().expect("entries").len(), 1);
+    }
+
+    #[test]
+    fn rejected_jar_is_not_cached() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let cache = JarCache::new(temp_dir.path().join("cache"));
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/velocity.jar");
+            then.status(200).body(JAR_BYTES);
+        });
+        let version = version_for(server.url("/velocity.jar"));
+        let dest = temp_dir.path().join("velocity.jar");
+
+        // JAR_BYTES は zip ではないため MANIFEST.MF の確認に失敗する
+        let result = test_downloader()
+            .with_cache(Some(cache.clone()))
+            .with_manifest_check(ManifestCheck::Fail)
+            .download_verified(&version, &dest);
+
+        assert!(result.is_err());
+        assert!(!dest.exists());
+        assert!(!temp_dir.path().join("velocity.jar.part").exists());
+        assert!(cache.entries().expect("entries").is_empty());
+    }
+
+    #[test]
+    fn retries_and_resumes_after_dropped_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let server = thread::spawn(move || {
+            let mut ranges = Vec::new();
+            for attempt in 0..2 {
+                let (mut stream, _) = listener.accept().expect("accept");
+                let mut reader = std::io::BufReader::new(stream.try_clone().expect("clone"));
+                let mut range = None;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).expect("read request");
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("range: ") {
+                        range = Some(value.trim().to_string());
+                    }
+                }
+                ranges.push(range);
+                if attempt == 0 {
+                    // Content-Length より短いところで接続を切る。
+                    let head = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                        JAR_BYTES.len()
+                    );
+                    stream.write_all(head.as_bytes()).expect("write head");
+                    stream.write_all(&JAR_BYTES[..10]).expect("write body");
+                } else {
+                    let head = format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes 10-{}/{}\r\nConnection: close\r\n\r\n",
+                        JAR_BYTES.len() - 10,
+                        JAR_BYTES.len() - 1,
+                        JAR_BYTES.len()
+                    );
+                    stream.write_all(head.as_bytes()).expect("write head");
+                    stream.write_all(&JAR_BYTES[10..]).expect("write body");
+                }
+            }
+            ranges
+        });
+
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let dest = temp_dir.path().join("velocity.jar");
+        let version = version_for(format!("http://{addr}/velocity.jar"));
+        test_downloader()
+            .download_verified(&version, &dest)
+            .expect("download");
+
+        let ranges = server.join().expect("server thread");
+        assert_eq!(ranges, vec![None, Some("bytes=10-".to_string())]);
+        assert_eq!(fs::read(&dest).expect("read jar"), JAR_BYTES);
+    }
+}
@@ -0,0 +1,71 @@
+use std::error::Error;
+use std::fs;
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+use reqwest::{Certificate, NoProxy, Proxy};
+
+use crate::config::HttpConfig;
+
+const DEFAULT_CONNECT_TIMEOUT: u64 = 10;
+const DEFAULT_TIMEOUT: u64 = 30;
+
+/// 設定から HTTP クライアントを組み立てる。
+///
+/// プロキシを明示しない場合は HTTP_PROXY / HTTPS_PROXY / NO_PROXY 環境変数に従う。
+pub fn build_client(
+    config: &HttpConfig,
+    default_user_agent: &str,
+) -> Result<Client, Box<dyn Error>> {
+    let connect_timeout = config.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT);
+    let timeout = config.timeout.unwrap_or(DEFAULT_TIMEOUT);
+    let user_agent = config.user_agent.as_deref().unwrap_or(default_user_agent);
+    let mut builder = Client::builder()
+        .user_agent(user_agent)
+        .connect_timeout(Duration::from_secs(connect_timeout))
+        .timeout(Duration::from_secs(timeout));
+
+    if let Some(proxy_url) = &config.proxy {
+        let proxy = Proxy::all(proxy_url)
+            .map_err(|err| format!("プロキシの URL が不正です: {proxy_url} ({err})"))?
+            .no_proxy(no_proxy(config));
+        builder = builder.proxy(proxy);
+    } else if config.no_proxy.is_some() {
+        // 除外リストだけを指定した場合は、環境変数のプロキシに除外リストを付けて使う。
+        if let Some(proxy_url) = env_proxy(&["HTTPS_PROXY", "https_proxy"]) {
+            builder = builder.proxy(Proxy::https(&proxy_url)?.no_proxy(no_proxy(config)));
+        }
+        if let Some(proxy_url) = env_proxy(&["HTTP_PROXY", "http_proxy"]) {
+            builder = builder.proxy(Proxy::http(&proxy_url)?.no_proxy(no_proxy(config)));
+        }
+    }
+
+    if let Some(path) = &config.ca_bundle {
+        let pem = fs::read(path)
+            .map_err(|err| format!("CA 証明書を読み込めません: {} ({err})", path.display()))?;
+        let certificates = Certificate::from_pem_bundle(&pem)
+            .map_err(|err| format!("CA 証明書の形式が不正です: {} ({err})", path.display()))?;
+        if certificates.is_empty() {
+            return Err(format!("CA 証明書が含まれていません: {}", path.display()).into());
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    Ok(builder.build()?)
+}
+
+fn no_proxy(config: &HttpConfig) -> Option<NoProxy> {
+    match &config.no_proxy {
+        Some(list) => NoProxy::from_string(list),
+        None => NoProxy::from_env(),
+    }
+}
+
+fn env_proxy(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
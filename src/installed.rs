@@ -0,0 +1,284 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::manifest::JarManifest;
+use crate::start_script::{Shell, StartScript};
+use crate::state::InstallState;
+use crate::version::{VelocityVersion, VersionInfo};
+
+/// 起動スクリプトを探す順。
+const START_SCRIPTS: [&str; 2] = ["start.sh", "start.bat"];
+
+/// インストール先から読み取った、インストール済みの Velocity。
+#[derive(Debug, Clone, Serialize)]
+pub struct InstalledVersion {
+    pub install_dir: PathBuf,
+    /// jar 名を読み取った起動スクリプト。
+    pub script: String,
+    /// 起動スクリプトに書かれた jar（書かれたまま）。
+    pub jar: String,
+    pub jar_path: PathBuf,
+    pub version: Option<String>,
+    pub build: Option<u64>,
+    /// バージョンを何から読み取ったか。
+    pub detected_from: Option<DetectedFrom>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DetectedFrom {
+    /// jar の `MANIFEST.MF` の `Implementation-Version`。
+    Manifest,
+    /// 状態ファイル（記録した jar と起動スクリプトの jar が同じ場合）。
+    State,
+    /// jar のファイル名（`velocity-3.4.0-SNAPSHOT-500.jar` など）。
+    FileName,
+}
+
+impl fmt::Display for DetectedFrom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Manifest => f.write_str("MANIFEST.MF"),
+            Self::State => f.write_str("状態ファイル"),
+            Self::FileName => f.write_str("jar のファイル名"),
+        }
+    }
+}
+
+impl InstalledVersion {
+    /// `install_dir` の起動スクリプトが起動する jar から、バージョンと build を読み取る。
+    ///
+    /// MANIFEST.MF → 状態ファイル → jar のファイル名の順に調べる。
+    /// 起動スクリプトが無い場合は `None`。jar が無い・読めない場合はバージョン不明として返す。
+    pub fn detect(install_dir: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        let Some((script, jar)) = script_jar(install_dir)? else {
+            return Ok(None);
+        };
+        let jar_path = if Path::new(&jar).is_absolute() {
+            PathBuf::from(&jar)
+        } else {
+            install_dir.join(&jar)
+        };
+        let from_manifest = JarManifest::read(&jar_path)
+            .ok()
+            .and_then(|manifest| manifest.implementation_version)
+            .map(|value| (parse_implementation_version(&value), DetectedFrom::Manifest));
+        // 起動スクリプトが記録後に書き換えられていれば、記録は別の jar のもの
+        let recorded = InstallState::load(install_dir)
+            .ok()
+            .flatten()
+            .filter(|state| state.jar.as_deref() == Some(jar.as_str()))
+            .and_then(|state| state.version.map(|version| (version, state.build)));
+        let from_manifest = from_manifest.map(|((version, build), from)| {
+            // MANIFEST.MF に build が無い場合は、同じバージョンの記録から補う
+            let build = build.or_else(|| {
+                recorded
+                    .as_ref()
+                    .filter(|(recorded, _)| *recorded == version)
+                    .and_then(|(_, build)| *build)
+            });
+            ((version, build), from)
+        });
+        let from_state = recorded.map(|recorded| (recorded, DetectedFrom::State));
+        let detected = from_manifest.or(from_state).or_else(|| {
+            jar_path
+                .file_name()
+                .and_then(|name| parse_jar_file_name(&name.to_string_lossy()))
+                .map(|parsed| (parsed, DetectedFrom::FileName))
+        });
+        let (version, build, detected_from) = match detected {
+            Some(((version, build), from)) => (Some(version), build, Some(from)),
+            None => (None, None, None),
+        };
+        Ok(Some(Self {
+            install_dir: install_dir.to_path_buf(),
+            script: script.to_string(),
+            jar,
+            jar_path,
+            version,
+            build,
+            detected_from,
+        }))
+    }
+
+    /// `version` がこれより新しいか。バージョンが分からない場合は新しいとみなす。
+    ///
+    /// 同じバージョンでは、どちらも build が分かる場合だけ build で比べる。
+    pub fn is_older_than(&self, version: &VersionInfo) -> bool {
+        let Some(current) = &self.version else {
+            return true;
+        };
+        match VelocityVersion::parse(&version.version).cmp(&VelocityVersion::parse(current)) {
+            Ordering::Greater => true,
+            Ordering::Less => false,
+            Ordering::Equal => {
+                matches!((version.build, self.build), (Some(new), Some(current)) if new > current)
+            }
+        }
+    }
+
+    /// `version` と同じものがインストールされているか。build はどちらも分かる場合だけ比べる。
+    pub fn is_same_as(&self, version: &VersionInfo) -> bool {
+        self.version.as_deref() == Some(version.version.as_str())
+            && !matches!((version.build, self.build), (Some(new), Some(current)) if new != current)
+    }
+
+    /// `3.4.0-SNAPSHOT (build 500)` の形。バージョンが分からない場合は jar 名。
+    pub fn label(&self) -> String {
+        match (&self.version, self.build) {
+            (Some(version), Some(build)) => format!("{version} (build {build})"),
+            (Some(version), None) => version.clone(),
+            (None, _) => format!("不明（{}）", self.jar),
+        }
+    }
+}
+
+/// 起動スクリプトの名前と、そこに書かれた jar。
+pub fn script_jar(install_dir: &Path) -> Result<Option<(&'static str, String)>, Box<dyn Error>> {
+    for script in START_SCRIPTS {
+        let path = install_dir.join(script);
+        if !path.exists() {
+            continue;
+        }
+        if let Some(jar) =
+            extract_jar_from_script(&fs::read_to_string(&path)?, Shell::from_path(&path))
+        {
+            return Ok(Some((script, jar)));
+        }
+    }
+    Ok(None)
+}
+
+/// start.sh / start.bat が参照する jar（書かれたまま）。
+pub fn script_jars(install_dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut jars = Vec::new();
+    for script in START_SCRIPTS {
+        let path = install_dir.join(script);
+        if path.exists()
+            && let Some(jar) =
+                extract_jar_from_script(&fs::read_to_string(&path)?, Shell::from_path(&path))
+        {
+            jars.push(jar);
+        }
+    }
+    Ok(jars)
+}
+
+pub fn extract_jar_from_script(contents: &str, shell: Shell) -> Option<String> {
+    StartScript::parse(contents, shell).map(|script| script.command().jar)
+}
+
+/// 起動スクリプト（start.sh → start.bat）の `-Xms` / `-Xmx`。
+pub fn detect_existing_memory(
+    install_dir: &Path,
+) -> Result<Option<(String, String)>, Box<dyn Error>> {
+    let sh_path = install_dir.join("start.sh");
+    if let Some(values) = read_memory_from_script(&sh_path)? {
+        return Ok(Some(values));
+    }
+    let bat_path = install_dir.join("start.bat");
+    if let Some(values) = read_memory_from_script(&bat_path)? {
+        return Ok(Some(values));
+    }
+    Ok(None)
+}
+
+fn read_memory_from_script(path: &Path) -> Result<Option<(String, String)>, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path)?;
+    Ok(extract_memory_flags(&contents, Shell::from_path(path)))
+}
+
+fn extract_memory_flags(contents: &str, shell: Shell) -> Option<(String, String)> {
+    StartScript::parse(contents, shell)?.command().memory()
+}
+
+/// `3.4.0-SNAPSHOT (git-1a2b3c4d-b500)` からバージョンと build を読み取る。
+fn parse_implementation_version(value: &str) -> (String, Option<u64>) {
+    let version = value.split_whitespace().next().unwrap_or(value).to_string();
+    let build = value
+        .split_once('(')
+        .and_then(|(_, detail)| detail.trim_end().strip_suffix(')'))
+        .and_then(|detail| detail.rsplit_once("-b"))
+        .and_then(|(_, build)| build.parse().ok());
+    (version, build)
+}
+
+/// `velocity-3.4.0-SNAPSHOT-500.jar`（PaperMC）や `velocity-proxy-3.4.0.jar` からバージョンと build を読み取る。
+fn parse_jar_file_name(name: &str) -> Option<(String, Option<u64>)> {
+    let stem = name.strip_suffix(".jar")?;
+    let rest = stem
+        .strip_prefix("velocity-proxy-")
+        .or_else(|| stem.strip_prefix("velocity-"))?;
+    if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    match rest.rsplit_once('-') {
+        Some((version, build))
+            if version.contains('.') && build.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            Some((version.to_string(), build.parse().ok()))
+        }
+        _ => Some((rest.to_string(), None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_version_from_manifest_and_file_name() {
+        assert_eq!(
+            parse_implementation_version("3.4.0-SNAPSHOT (git-1a2b3c4d-b500)"),
+            ("3.4.0-SNAPSHOT".to_string(), Some(500))
+        );
+        assert_eq!(
+            parse_implementation_version("3.3.0"),
+            ("3.3.0".to_string(), None)
+        );
+        assert_eq!(
+            parse_jar_file_name("velocity-3.4.0-SNAPSHOT-500.jar"),
+            Some(("3.4.0-SNAPSHOT".to_string(), Some(500)))
+        );
+        assert_eq!(
+            parse_jar_file_name("velocity-proxy-3.4.0.jar"),
+            Some(("3.4.0".to_string(), None))
+        );
+        assert_eq!(parse_jar_file_name("server.jar"), None);
+    }
+
+    #[test]
+    fn detect_reads_jar_from_start_script() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        fs::write(
+            temp_dir.path().join("start.sh"),
+            "#!/usr/bin/env sh\nexec java -Xms1G -Xmx1G -jar \"velocity-3.4.0-SNAPSHOT-500.jar\"\n",
+        )
+        .expect("write script");
+
+        let installed = InstalledVersion::detect(temp_dir.path())
+            .expect("detect")
+            .expect("installed");
+        assert_eq!(installed.script, "start.sh");
+        assert_eq!(installed.jar, "velocity-3.4.0-SNAPSHOT-500.jar");
+        assert_eq!(installed.version.as_deref(), Some("3.4.0-SNAPSHOT"));
+        assert_eq!(installed.build, Some(500));
+        assert_eq!(installed.detected_from, Some(DetectedFrom::FileName));
+        assert_eq!(installed.label(), "3.4.0-SNAPSHOT (build 500)");
+
+        let empty = tempfile::TempDir::new().expect("temp dir");
+        assert!(
+            InstalledVersion::detect(empty.path())
+                .expect("detect")
+                .is_none()
+        );
+    }
+}
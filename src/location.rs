@@ -0,0 +1,43 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use reqwest::Url;
+
+/// `file://` URL、または URL として解釈できない文字列（`C:\` のようなドライブ名を含む）は
+/// ローカルのパスとして扱う。
+pub fn local_path(location: &str) -> Option<PathBuf> {
+    match Url::parse(location) {
+        Ok(parsed) if parsed.scheme() == "file" => parsed.to_file_path().ok(),
+        Ok(parsed) if parsed.scheme().len() == 1 => Some(PathBuf::from(location)),
+        Ok(_) => None,
+        Err(_) => Some(PathBuf::from(location)),
+    }
+}
+
+/// 取得元の場所を URL にする。ローカルのパスは絶対パスの `file://` URL に変換する。
+pub fn location_url(location: &str) -> Result<Url, Box<dyn Error>> {
+    match local_path(location) {
+        Some(path) => file_url(&path),
+        None => Ok(Url::parse(location)?),
+    }
+}
+
+pub fn file_url(path: &Path) -> Result<Url, Box<dyn Error>> {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+    Url::from_file_path(&absolute)
+        .map_err(|_| format!("パスを URL に変換できません: {}", path.display()).into())
+}
+
+/// `reference` が相対 URL / 相対パスなら `base` を基準に解決する。
+pub fn resolve(base: &Url, reference: &str) -> Result<Url, Box<dyn Error>> {
+    match Url::parse(reference) {
+        Ok(url) if url.scheme().len() > 1 => Ok(url),
+        // `C:\...` のようなドライブ名付きのパス
+        Ok(_) => file_url(Path::new(reference)),
+        Err(_) => Ok(base.join(reference)?),
+    }
+}
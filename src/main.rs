@@ -1,19 +1,83 @@
 use std::error::Error;
-use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
-use reqwest::{Url, blocking::Client};
-use sha2::{Digest, Sha256};
+use reqwest::blocking::Client;
 
+mod backup;
+mod cache;
+mod checksum;
+mod config;
+mod download;
+mod http;
+mod installed;
+mod location;
+mod manifest;
+mod progress;
 mod prompts;
+mod retention;
+mod signature;
+mod start_script;
+mod state;
 mod version;
 
+use backup::Backup;
+use cache::{IndexCache, JarCache, default_cache_dir, format_timestamp, unix_now};
+use checksum::{Algorithm, Checksum, Checksums};
+use config::{
+    CacheConfig, ChecksumConfig, Config, HttpConfig, IndexConfig, JarConfig, MirrorConfig,
+    SignatureConfig, parse_count, parse_seconds,
+};
+use download::{Downloader, jar_filename_from_url};
+use installed::{InstalledVersion, detect_existing_memory, extract_jar_from_script, script_jars};
+use manifest::ManifestCheck;
+use progress::{ProgressStyle, format_bytes};
 use prompts::{
-    confirm_existing_install, prompt_deploy_source_dir, prompt_install_dir, prompt_memory,
-    prompt_version, prompt_yes_no,
+    Answers, UpdateTimer, confirm_existing_install, prompt_confirm, prompt_deploy_source_dir,
+    prompt_install_dir, prompt_memory, prompt_update_timer, prompt_version, prompt_yes_no_answer,
+};
+use retention::{prune_old_jars, unreferenced_jars};
+use signature::SignatureVerifier;
+use start_script::{JavaCommand, Shell, StartScript};
+use state::{InstallState, STATE_FILE_NAME, ServiceState, read_service};
+use version::{
+    MirroredSource, SourceKind, SourceOptions, VersionInfo, VersionSource, fetch_versions,
 };
-use version::{VERSION_INDEX_URL, VersionInfo, fetch_versions};
+
+/// エラーで終了する場合の終了コード（成功は 0）。
+const EXIT_FAILURE: i32 = 1;
+
+/// `check-updates` で更新がある場合の終了コード（最新なら 0）。
+const EXIT_UPDATE_AVAILABLE: i32 = 2;
+
+/// `cache prune` で `--older-than` を省略した場合の日数。
+const DEFAULT_CACHE_PRUNE_DAYS: u64 = 30;
+
+/// `upgrade` で検証が済むまで新しい jar を置く、インストール先の中のディレクトリ。
+const UPGRADE_STAGING_DIR_NAME: &str = ".mc-velocity-installer-upgrade";
+
+/// 自動更新のタイマーが実行するコマンドに引き継ぐ、値をとるフラグ（取得元・通信・検証の設定）。
+const UPDATE_VALUE_FLAGS: [&str; 10] = [
+    "--source",
+    "--connect-timeout",
+    "--timeout",
+    "--proxy",
+    "--no-proxy",
+    "--ca-bundle",
+    "--user-agent",
+    "--cache-dir",
+    "--jar-manifest-check",
+    "--keep-jars",
+];
+
+/// 自動更新のタイマーが実行するコマンドに引き継ぐ、値をとらないフラグ。
+const UPDATE_SWITCH_FLAGS: [&str; 4] = [
+    "--no-cache",
+    "--require-signature",
+    "--allow-weak-checksum",
+    "--skip-invalid-entries",
+];
 
 #[derive(Debug)]
 struct InstallSettings {
@@ -21,12 +85,15 @@ struct InstallSettings {
     version: VersionInfo,
     xms: String,
     xmx: String,
+    update_timer: Option<UpdateTimer>,
+    /// 自動更新のコマンドに引き継ぐフラグ（`--config` や `--source` など）。
+    update_options: Vec<String>,
 }
 
 fn main() {
     if let Err(err) = run() {
         eprintln!("エラー: {err}");
-        std::process::exit(1);
+        std::process::exit(EXIT_FAILURE);
     }
 }
 
@@ -36,52 +103,146 @@ fn run() -> Result<(), Box<dyn Error>> {
         print_help();
         return Ok(());
     }
-    if args.iter().any(|arg| arg == "--version" || arg == "-V") {
+    if wants_build_version(&args)? {
         print_version();
         return Ok(());
     }
+    let config = load_config(&args)?;
+    if args.first().map(String::as_str) == Some("list-versions") {
+        run_list_versions(&args[1..], &config)?;
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("cache") {
+        run_cache(&args[1..], &config.cache)?;
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("info") {
+        run_info(&args[1..])?;
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("check-updates") {
+        if run_check_updates(&args[1..], &config)? {
+            std::process::exit(EXIT_UPDATE_AVAILABLE);
+        }
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("prune") {
+        run_prune(&args[1..], config.jar.keep)?;
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("rollback") {
+        run_rollback(&parse_answers(&args[1..])?)?;
+        return Ok(());
+    }
+    let answers = parse_answers(&args)?;
+    let save_path = parse_option_value(&args, "--save-answers")?.map(PathBuf::from);
+    let save_path = save_path.as_deref();
     if let Some(deploy_dir) = parse_option_value(&args, "--deploy")? {
-        run_deploy(PathBuf::from(deploy_dir))?;
+        run_deploy(PathBuf::from(deploy_dir), &answers, save_path)?;
         return Ok(());
     }
-    if args.iter().any(|arg| arg == "--redownload-jar") {
-        run_redownload_jar()?;
+    let source = version_source(&args, &config)?;
+    let quiet = has_flag(&args, "--quiet") || has_flag(&args, "-q");
+    let downloader = Downloader::new(build_client(&config.http)?, ProgressStyle::detect(quiet))
+        .with_mirrors(config.mirrors.jar.clone().unwrap_or_default())
+        .with_cache(enabled_cache_dir(&config.cache).map(|dir| JarCache::in_cache_dir(&dir)))
+        .with_weak_checksum(config.checksum.allow_weak.unwrap_or(false))
+        .with_signature(SignatureVerifier::from_config(&config.signature)?)
+        .with_manifest_check(config.jar.manifest_check.unwrap_or(ManifestCheck::Warn));
+    if args.first().map(String::as_str) == Some("upgrade") {
+        run_upgrade(&answers, source.as_ref(), &downloader, config.jar.keep)?;
+        return Ok(());
+    }
+    if has_flag(&args, "--redownload-jar") {
+        run_redownload_jar(
+            &answers,
+            source.as_ref(),
+            &downloader,
+            save_path,
+            config.jar.keep,
+        )?;
         return Ok(());
     }
     println!("{} (新規インストール)", binary_name());
     println!("Java はインストール済みであることを前提に進めます。");
     println!();
 
-    let install_dir = prompt_install_dir()?;
-    if !confirm_existing_install(&install_dir)? {
+    let install_dir = prompt_install_dir(&answers)?;
+    let overwrite = confirm_existing_install(&install_dir, &answers)?;
+    if overwrite == Some(false) {
         println!("中断しました。");
         return Ok(());
     }
 
-    let client = build_client()?;
-
-    println!("Velocity のバージョン一覧を取得しています...");
-    let index_url =
-        std::env::var("MC_VELOCITY_INDEX_URL").unwrap_or_else(|_| VERSION_INDEX_URL.to_string());
-    let versions = fetch_versions(&client, &index_url)?;
-    let version = prompt_version(&versions)?;
+    let state = load_state(&install_dir)?;
+    let installed = detect_installed(&install_dir)?;
+    let version = choose_version(
+        downloader.client(),
+        source.as_ref(),
+        &answers,
+        installed.as_ref(),
+    )?;
 
-    let (xms, xmx) = prompt_memory()?;
+    let (xms, xmx) = prompt_memory(
+        &answers,
+        state.as_ref().and_then(InstallState::memory).as_ref(),
+    )?;
+    // 手元の jar は一覧から取得しないため、一覧を追う自動更新とは組み合わせない
+    let update_timer = match (&answers.jar, &answers.update_schedule) {
+        (Some(_), Some(_)) => {
+            return Err(
+                "--jar でインストールする場合は自動更新のタイマーを生成できません。".into(),
+            );
+        }
+        (Some(_), None) => None,
+        (None, _) => prompt_update_timer(&answers)?,
+    };
 
+    let update_options = match update_timer {
+        Some(_) => update_options(&args)?,
+        None => Vec::new(),
+    };
     let settings = InstallSettings {
         install_dir,
         version,
         xms,
         xmx,
+        update_timer,
+        update_options,
     };
 
-    print_summary(&settings);
-    if !prompt_yes_no("この内容で実行しますか？", true)? {
+    let checksum = downloader.checksum_for(&settings.version)?;
+    print_summary(&settings, &checksum);
+    if !prompt_confirm(&answers, "この内容で実行しますか？")? {
         println!("中断しました。");
         return Ok(());
     }
 
-    perform_install(&client, &settings)?;
+    let index_url = answers.jar.is_none().then(|| source.location());
+    perform_install(&downloader, &settings, index_url, state, config.jar.keep)?;
+    save_answers(
+        save_path,
+        &Answers {
+            install_dir: Some(settings.install_dir.clone()),
+            version: Some(settings.version.version.clone()),
+            build: settings.version.build,
+            checksums: settings.version.checksums.clone(),
+            jar: answers.jar.clone(),
+            xms: Some(settings.xms.clone()),
+            xmx: Some(settings.xmx.clone()),
+            update_schedule: settings
+                .update_timer
+                .as_ref()
+                .map(|timer| timer.schedule.clone()),
+            update_kind: settings
+                .update_timer
+                .as_ref()
+                .map(|timer| timer.kind.clone()),
+            overwrite,
+            assume_yes: true,
+            ..Answers::default()
+        },
+    )?;
     println!();
     println!("完了しました。");
     println!(
@@ -96,10 +257,297 @@ fn run() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+fn run_list_versions(args: &[String], config: &Config) -> Result<(), Box<dyn Error>> {
+    let kind = parse_option_value(args, "--kind")?;
+    let limit = match parse_option_value(args, "--limit")? {
+        Some(value) => Some(
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("--limit には数値を指定してください: {value}"))?,
+        ),
+        None => None,
+    };
+    let format = parse_option_value(args, "--format")?.unwrap_or_else(|| "table".to_string());
+    if format != "table" && format != "json" {
+        return Err(format!("--format には table か json を指定してください: {format}").into());
+    }
+
+    let source = version_source(args, config)?;
+    let client = build_client(&config.http)?;
+    let versions: Vec<VersionInfo> = fetch_versions(&client, source.as_ref())?
+        .into_iter()
+        .filter(|version| kind.as_ref().is_none_or(|kind| &version.kind == kind))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&versions)?);
+    } else {
+        print_version_table(&versions);
+    }
+    Ok(())
+}
+
+fn run_cache(args: &[String], config: &CacheConfig) -> Result<(), Box<dyn Error>> {
+    let dir = cache_dir(config)
+        .ok_or("キャッシュの場所を決められません（--cache-dir を指定してください）")?;
+    let cache = JarCache::in_cache_dir(&dir);
+    match args.first().map(String::as_str) {
+        Some("list") => {
+            let entries = cache.entries()?;
+            println!("キャッシュ: {}", cache.dir().display());
+            if entries.is_empty() {
+                println!("キャッシュされた jar はありません。");
+                return Ok(());
+            }
+            println!(
+                "{:<12}  {:>10}  {:<10}  JAR",
+                "CHECKSUM", "SIZE", "LAST USED"
+            );
+            let now = SystemTime::now();
+            for entry in &entries {
+                let name = entry
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let age = now.duration_since(entry.modified).unwrap_or_default();
+                println!(
+                    "{:<12}  {:>10}  {:<10}  {name}",
+                    &entry.digest[..12],
+                    format_bytes(entry.size),
+                    format_age(age)
+                );
+            }
+        }
+        Some("prune") => {
+            let days = match parse_option_value(args, "--older-than")? {
+                Some(value) => value
+                    .parse::<u64>()
+                    .map_err(|_| format!("--older-than には日数を指定してください: {value}"))?,
+                None => DEFAULT_CACHE_PRUNE_DAYS,
+            };
+            let removed = cache.prune(Duration::from_secs(days * 24 * 60 * 60))?;
+            for path in &removed {
+                println!("削除しました: {}", path.display());
+            }
+            println!(
+                "{} 件を削除しました（{days} 日以上使っていない jar と壊れた jar）。",
+                removed.len()
+            );
+        }
+        Some("clear") => {
+            let count = cache.clear()?;
+            IndexCache::in_cache_dir(&dir).clear()?;
+            println!(
+                "{count} 件の jar とバージョン一覧のキャッシュを削除しました: {}",
+                dir.display()
+            );
+        }
+        _ => return Err("cache には list / prune / clear のいずれかを指定してください。".into()),
+    }
+    Ok(())
+}
+
+fn run_info(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let install_dir = parse_option_value(args, "--dir")?
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let format = parse_option_value(args, "--format")?.unwrap_or_else(|| "text".to_string());
+    if format != "text" && format != "json" {
+        return Err(format!("--format には text か json を指定してください: {format}").into());
+    }
+    let installed = InstalledVersion::detect(&install_dir)?.ok_or_else(|| {
+        format!(
+            "Velocity のインストールが見つかりません（start.sh / start.bat がありません）: {}",
+            install_dir.display()
+        )
+    })?;
+    let state = InstallState::load(&install_dir)?;
+    let drift = match &state {
+        Some(state) => state.drift(&install_dir)?,
+        None => Vec::new(),
+    };
+    if format == "json" {
+        let mut report = serde_json::to_value(&installed)?;
+        report["state"] = serde_json::to_value(&state)?;
+        report["drift"] = serde_json::to_value(&drift)?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    println!("インストール先: {}", absolute_path(&install_dir)?.display());
+    println!("起動スクリプト: {}", installed.script);
+    println!("jar: {}", installed.jar);
+    if !installed.jar_path.exists() {
+        println!(
+            "  （jar が見つかりません: {}）",
+            installed.jar_path.display()
+        );
+    }
+    println!("バージョン: {}", installed.label());
+    if let Some(detected_from) = installed.detected_from {
+        println!("読み取り元: {detected_from}");
+    }
+    match &state {
+        Some(state) => {
+            println!(
+                "記録: {STATE_FILE_NAME}（最終更新 {}）",
+                format_timestamp(state.updated_at)
+            );
+            if drift.is_empty() {
+                println!("記録との差異: なし");
+            }
+            for drift in &drift {
+                println!("記録との差異: {drift}");
+            }
+        }
+        None => println!("記録: なし（{STATE_FILE_NAME} がありません）"),
+    }
+    Ok(())
+}
+
+/// インストール済みのバージョンと一覧の最新を比べる。更新があれば `true`。
+///
+/// 監視や cron から使うため、対話はしない。
+fn run_check_updates(args: &[String], config: &Config) -> Result<bool, Box<dyn Error>> {
+    let install_dir = parse_option_value(args, "--dir")?
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let kind = parse_option_value(args, "--kind")?;
+    let format = parse_option_value(args, "--format")?.unwrap_or_else(|| "text".to_string());
+    if format != "text" && format != "json" {
+        return Err(format!("--format には text か json を指定してください: {format}").into());
+    }
+    let installed = InstalledVersion::detect(&install_dir)?.ok_or_else(|| {
+        format!(
+            "Velocity のインストールが見つかりません（start.sh / start.bat がありません）: {}",
+            install_dir.display()
+        )
+    })?;
+    if installed.version.is_none() {
+        return Err(format!(
+            "インストール済みのバージョンを読み取れません: {}",
+            installed.jar
+        )
+        .into());
+    }
+
+    let source = version_source(args, config)?;
+    let client = build_client(&config.http)?;
+    let latest = fetch_versions(&client, source.as_ref())?
+        .into_iter()
+        .find(|version| kind.as_ref().is_none_or(|kind| &version.kind == kind))
+        .ok_or_else(|| match &kind {
+            Some(kind) => format!("type が {kind} のバージョンがありません。"),
+            None => "バージョン一覧が空です。".to_string(),
+        })?;
+    let update_available = installed.is_older_than(&latest);
+
+    if format == "json" {
+        let report = serde_json::json!({
+            "install_dir": install_dir,
+            "installed": {
+                "version": installed.version,
+                "build": installed.build,
+            },
+            "latest": latest,
+            "update_available": update_available,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(update_available);
+    }
+    let latest_label = match latest.build {
+        Some(build) => format!("{} ({}, build {build})", latest.version, latest.kind),
+        None => format!("{} ({})", latest.version, latest.kind),
+    };
+    println!("インストール済み: {}", installed.label());
+    println!("最新: {latest_label}");
+    if update_available {
+        println!("更新があります: {} → {latest_label}", installed.label());
+    } else {
+        println!("最新です。");
+    }
+    Ok(update_available)
+}
+
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    if seconds >= 24 * 60 * 60 {
+        format!("{} 日前", seconds / (24 * 60 * 60))
+    } else if seconds >= 60 * 60 {
+        format!("{} 時間前", seconds / (60 * 60))
+    } else {
+        format!("{} 分前", seconds / 60)
+    }
+}
+
+fn print_version_table(versions: &[VersionInfo]) {
+    let version_width = versions
+        .iter()
+        .map(|version| version.version.len())
+        .chain(["VERSION".len()])
+        .max()
+        .unwrap_or_default();
+    let kind_width = versions
+        .iter()
+        .map(|version| version.kind.len())
+        .chain(["KIND".len()])
+        .max()
+        .unwrap_or_default();
+    println!(
+        "{:<version_width$}  {:<kind_width$}  {:>5}  URL",
+        "VERSION", "KIND", "BUILD"
+    );
+    for version in versions {
+        let build = version
+            .build
+            .map(|build| build.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<version_width$}  {:<kind_width$}  {:>5}  {}",
+            version.version, version.kind, build, version.url
+        );
+    }
+}
+
+/// `--source` / `MC_VELOCITY_INDEX_SOURCE` が無ければ、取得元の種類を URL から判定する。
+///
+/// ミラーがあれば本来の URL に続けて試す。ミラーも同じ種類の取得元として扱う。
+fn version_source(
+    args: &[String],
+    config: &Config,
+) -> Result<Box<dyn VersionSource>, Box<dyn Error>> {
+    let explicit = match parse_option_value(args, "--source")? {
+        Some(name) => Some(name),
+        None => std::env::var("MC_VELOCITY_INDEX_SOURCE").ok(),
+    };
+    let url = std::env::var("MC_VELOCITY_INDEX_URL").ok();
+    let kind = match (explicit, &url) {
+        (Some(name), _) => SourceKind::parse(&name)?,
+        (None, Some(url)) => SourceKind::detect(url),
+        (None, None) => SourceKind::VelocityIndex,
+    };
+    let url = url.unwrap_or_else(|| kind.default_url().to_string());
+    let options = SourceOptions {
+        cache: enabled_cache_dir(&config.cache).map(|dir| IndexCache::in_cache_dir(&dir)),
+        skip_invalid_entries: config.index.skip_invalid_entries.unwrap_or(false),
+        signature: SignatureVerifier::from_config(&config.signature)?,
+    };
+    let index_mirrors = config.mirrors.index.as_deref().unwrap_or_default();
+    if index_mirrors.is_empty() {
+        return Ok(kind.source(&url, &options));
+    }
+    let sources = std::iter::once(url.as_str())
+        .chain(index_mirrors.iter().map(String::as_str))
+        .map(|url| kind.source(url, &options))
+        .collect();
+    Ok(Box::new(MirroredSource::new(sources)))
+}
+
 fn print_help() {
     let name = binary_name();
     println!(
-        "{name} {}\n\n使い方:\n  {name} [OPTIONS]\n\nOPTIONS:\n  --deploy <DIR>     指定先へデプロイします\n  --redownload-jar   jar を再取得します（必要ならスクリプト置き換え）\n  -h, --help         ヘルプを表示します\n  -V, --version      バージョンを表示します\n\n詳細・更新情報:\n  ドキュメントや最新の変更点は以下で確認できます。\n  https://github.com/dimgraycat/mc-velocity-installer\n",
+        "{name} {}\n\n使い方:\n  {name} [OPTIONS]\n  {name} list-versions [--kind <KIND>] [--limit <N>] [--format table|json] [--source <SOURCE>]\n  {name} cache list|prune [--older-than <DAYS>]|clear\n  {name} info [--dir <DIR>] [--format text|json]\n  {name} upgrade [--dir <DIR>] [--version <VERSION>]\n  {name} rollback [--dir <DIR>]\n  {name} check-updates [--dir <DIR>] [--kind <KIND>] [--format text|json]\n  {name} prune [--dir <DIR>] [--keep-jars <N>]\n\nOPTIONS:\n  --deploy <DIR>       指定先へデプロイします\n  --redownload-jar     jar を再取得します（必要ならスクリプト置き換え）\n  --dir <DIR>          インストール先（--deploy ではデプロイ元）を指定します\n  --version <VERSION>  Velocity のバージョンを指定します\n  --jar <PATH>         ダウンロードせずに手元の jar を使います（--sha256 などが必要）\n  --sha256 <HEX>       jar の sha256 を指定して検証します\n  --sha512 <HEX>       jar の sha512 を指定して検証します\n  --sha1 <HEX>         jar の sha1 を指定します（--allow-weak-checksum が必要）\n  --allow-weak-checksum  sha1 しか無い jar を sha1 で検証します\n  --require-signature  署名を検証できない一覧・jar を使いません（公開鍵の設定が必要）\n  --jar-manifest-check <warn|fail|off>  jar の MANIFEST.MF が選んだバージョンと違う場合の扱い（既定 warn）\n  --keep-jars <N>      参照されていない古い jar を新しい N 個だけ残します（取得後と prune）\n  --xms <SIZE>         起動メモリ Xms を指定します\n  --xmx <SIZE>         最大メモリ Xmx を指定します\n  -q, --quiet          ダウンロードの進捗を表示しません\n  -y, --yes            確認をすべて承認します\n  --overwrite[=false]  既存ファイルを上書きします（false で上書きしません）\n  --replace-scripts[=false]  --redownload-jar で start.sh / start.bat を置き換えます（false で置き換えません）\n  --update-schedule <CALENDAR>  自動更新の systemd タイマーを生成します（OnCalendar の値。例: weekly）\n  --update-kind <KIND>  自動更新で追う type（既定 stable）\n  --non-interactive    対話せずに実行します（未指定の回答はエラー）\n  --answers <FILE>     回答ファイルの内容で回答します\n  --save-answers <FILE>  今回の回答を回答ファイルに保存します\n  --source <SOURCE>    バージョン一覧の取得元（velocity-index / papermc）\n  --config <FILE>      設定ファイルを指定します\n  --connect-timeout <SEC>  接続のタイムアウト秒数（既定 10）\n  --timeout <SEC>      読み込みなどのタイムアウト秒数（既定 30）\n  --proxy <URL>        HTTP(S) プロキシを指定します\n  --no-proxy <HOSTS>   プロキシを使わないホスト（カンマ区切り）\n  --ca-bundle <FILE>   追加で信頼する CA 証明書（PEM）\n  --user-agent <UA>    User-Agent を指定します\n  --cache-dir <DIR>    jar のキャッシュの場所を指定します\n  --no-cache           jar・バージョン一覧のキャッシュを使いません\n  --skip-invalid-entries  バージョン一覧の不正なエントリを除外して続行します\n  -h, --help           ヘルプを表示します\n  -V, --version        バージョンを表示します\n\n終了コード:\n  0  成功（check-updates では更新なし）\n  {EXIT_FAILURE}  エラー\n  {EXIT_UPDATE_AVAILABLE}  check-updates で更新がある\n\n詳細・更新情報:\n  ドキュメントや最新の変更点は以下で確認できます。\n  https://github.com/dimgraycat/mc-velocity-installer\n",
         build_version()
     );
 }
@@ -127,103 +575,448 @@ fn build_version() -> String {
         .to_string()
 }
 
-fn print_summary(settings: &InstallSettings) {
+fn print_summary(settings: &InstallSettings, checksum: &Checksum) {
     println!();
     println!("設定サマリ:");
     println!("- インストール先: {}", settings.install_dir.display());
     println!("- バージョン: {}", settings.version.display_label());
+    print_checksum(checksum);
     println!("- 起動メモリ: Xms={} / Xmx={}", settings.xms, settings.xmx);
+    if let Some(timer) = &settings.update_timer {
+        println!(
+            "- 自動更新: {}（type {} の最新に更新）",
+            timer.schedule, timer.kind
+        );
+    }
     println!("- 設定ファイルは初回起動時に生成されます");
 }
 
-fn print_redownload_summary(install_dir: &Path, version: &VersionInfo, jar_name: &str) {
+fn print_redownload_summary(
+    install_dir: &Path,
+    installed: Option<&InstalledVersion>,
+    version: &VersionInfo,
+    checksum: &Checksum,
+    jar_name: &str,
+) {
     println!();
     println!("設定サマリ:");
     println!("- インストール先: {}", install_dir.display());
-    println!("- バージョン: {}", version.display_label());
+    match installed {
+        Some(installed) => println!(
+            "- バージョン: {} → {}",
+            installed.label(),
+            version.display_label()
+        ),
+        None => println!("- バージョン: {}", version.display_label()),
+    }
+    print_checksum(checksum);
     println!("- 再取得する jar: {jar_name}");
     println!("- 既存スクリプトの置き換え可否は後で確認します");
 }
 
-fn perform_install(client: &Client, settings: &InstallSettings) -> Result<(), Box<dyn Error>> {
+fn print_checksum(checksum: &Checksum) {
+    if checksum.algorithm.is_weak() {
+        println!("- チェックサム: {checksum}（弱いハッシュ。明示的に許可されています）");
+    } else {
+        println!("- チェックサム: {checksum}");
+    }
+}
+
+fn perform_install(
+    downloader: &Downloader,
+    settings: &InstallSettings,
+    index_url: Option<&str>,
+    previous: Option<InstallState>,
+    keep_jars: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
     if !settings.install_dir.exists() {
         fs::create_dir_all(&settings.install_dir)?;
     }
 
     let jar_name = jar_filename_from_url(&settings.version.url, &settings.version.version);
     let jar_path = settings.install_dir.join(&jar_name);
-    println!("ダウンロード中: {}", settings.version.url);
-    download_with_sha256(client, &settings.version, &jar_path)?;
+    downloader.download_verified(&settings.version, &jar_path)?;
 
     write_start_scripts(
         &settings.install_dir,
-        &settings.xms,
-        &settings.xmx,
-        &jar_name,
+        &JavaCommand::new(&settings.xms, &settings.xmx, &jar_name),
     )?;
-    write_systemd_service(settings)?;
-    Ok(())
+    let service = write_systemd_service(settings)?;
+    if let (Some(timer), Some(index_url)) = (&settings.update_timer, index_url) {
+        write_update_timer(
+            &settings.install_dir,
+            &service,
+            timer,
+            index_url,
+            &settings.update_options,
+        )?;
+    }
+    apply_jar_retention(&settings.install_dir, &jar_name, keep_jars)?;
+    record_state(
+        &settings.install_dir,
+        previous,
+        &settings.version,
+        &jar_name,
+        index_url,
+        Some((settings.xms.clone(), settings.xmx.clone())),
+        Some(service),
+    )
 }
 
-fn run_redownload_jar() -> Result<(), Box<dyn Error>> {
+fn run_redownload_jar(
+    answers: &Answers,
+    source: &dyn VersionSource,
+    downloader: &Downloader,
+    save_path: Option<&Path>,
+    keep_jars: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
     println!("{} (jar再取得)", binary_name());
     println!("Java はインストール済みであることを前提に進めます。");
     println!();
 
-    let install_dir = prompt_install_dir()?;
-    if !confirm_existing_install(&install_dir)? {
+    let install_dir = prompt_install_dir(answers)?;
+    let overwrite = confirm_existing_install(&install_dir, answers)?;
+    if overwrite == Some(false) {
         println!("中断しました。");
         return Ok(());
     }
 
-    let client = build_client()?;
-    println!("Velocity のバージョン一覧を取得しています...");
-    let index_url =
-        std::env::var("MC_VELOCITY_INDEX_URL").unwrap_or_else(|_| VERSION_INDEX_URL.to_string());
-    let versions = fetch_versions(&client, &index_url)?;
-    let version = prompt_version(&versions)?;
+    let state = load_state(&install_dir)?;
+    let installed = detect_installed(&install_dir)?;
+    let version = choose_version(downloader.client(), source, answers, installed.as_ref())?;
 
     let jar_name = jar_filename_from_url(&version.url, &version.version);
-    print_redownload_summary(&install_dir, &version, &jar_name);
-    if !prompt_yes_no("この内容で再取得しますか？", true)? {
+    let checksum = downloader.checksum_for(&version)?;
+    print_redownload_summary(
+        &install_dir,
+        installed.as_ref(),
+        &version,
+        &checksum,
+        &jar_name,
+    );
+    if !prompt_confirm(answers, "この内容で再取得しますか？")? {
         println!("中断しました。");
         return Ok(());
     }
+    // 非対話モードで回答が無い場合に、jar を取得する前にエラーにする
+    let replace_scripts = prompt_yes_no_answer(
+        answers.replace_scripts,
+        answers.non_interactive,
+        "--replace-scripts（置き換えない場合は --replace-scripts=false）",
+        "start.sh / start.bat を置き換えますか？",
+        false,
+    )?;
 
     if !install_dir.exists() {
         fs::create_dir_all(&install_dir)?;
     }
 
     let jar_path = install_dir.join(&jar_name);
-    println!("ダウンロード中: {}", version.url);
-    download_with_sha256(&client, &version, &jar_path)?;
-    let replace_scripts = prompt_yes_no("start.sh / start.bat を置き換えますか？", false)?;
+    downloader.download_verified(&version, &jar_path)?;
+    let mut recorded = Answers {
+        install_dir: Some(install_dir.clone()),
+        version: Some(version.version.clone()),
+        build: version.build,
+        checksums: version.checksums.clone(),
+        jar: answers.jar.clone(),
+        overwrite,
+        assume_yes: true,
+        ..Answers::default()
+    };
+    recorded.replace_scripts = Some(replace_scripts);
+    let mut memory = None;
     if replace_scripts {
-        let (xms, xmx) = match detect_existing_memory(&install_dir)? {
-            Some(values) => values,
-            None => prompt_memory()?,
-        };
-        write_start_scripts(&install_dir, &xms, &xmx, &jar_name)?;
+        let requested = answers.xms.as_deref().zip(answers.xmx.as_deref());
+        // 状態ファイルから読み取ったメモリ値はホストごとに異なりうるため記録しない。
+        let recorded_memory = state.as_ref().and_then(InstallState::memory);
+        let fallback = recorded_memory
+            .as_ref()
+            .map(|(xms, xmx)| (xms.as_str(), xmx.as_str()));
+        // 既存のスクリプトは jar とメモリだけを変え、追加された JVM オプションや引数を残す。
+        // メモリの指定が無いスクリプトは状態ファイルの記録で補う
+        if !regenerate_start_scripts(&install_dir, &jar_name, requested, fallback)? {
+            let (xms, xmx) = match requested.or(fallback) {
+                Some((xms, xmx)) => (xms.to_string(), xmx.to_string()),
+                None => {
+                    let (xms, xmx) = prompt_memory(answers, None)?;
+                    recorded.xms = Some(xms.clone());
+                    recorded.xmx = Some(xmx.clone());
+                    (xms, xmx)
+                }
+            };
+            write_start_scripts(&install_dir, &JavaCommand::new(&xms, &xmx, &jar_name))?;
+        }
         println!("start.sh / start.bat を更新しました。");
+        memory = detect_existing_memory(&install_dir)?;
+    }
+    // 起動スクリプトが以前の jar を起動し続ける場合、記録は以前のまま残す
+    let launches_new_jar = replace_scripts
+        || installed
+            .as_ref()
+            .is_none_or(|installed| installed.jar == jar_name);
+    if launches_new_jar {
+        let index_url = answers.jar.is_none().then(|| source.location());
+        record_state(
+            &install_dir,
+            state,
+            &version,
+            &jar_name,
+            index_url,
+            memory,
+            None,
+        )?;
+    }
+    apply_jar_retention(&install_dir, &jar_name, keep_jars)?;
+    save_answers(save_path, &recorded)?;
+    println!();
+    println!("完了しました。");
+    Ok(())
+}
+
+fn run_upgrade(
+    answers: &Answers,
+    source: &dyn VersionSource,
+    downloader: &Downloader,
+    keep_jars: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    println!("{} (アップグレード)", binary_name());
+    println!("Java はインストール済みであることを前提に進めます。");
+    println!();
+
+    let install_dir = prompt_install_dir(answers)?;
+    let state = load_state(&install_dir)?;
+    let installed = detect_installed(&install_dir)?.ok_or_else(|| {
+        format!(
+            "Velocity のインストールが見つかりません（start.sh / start.bat がありません）: {}",
+            install_dir.display()
+        )
+    })?;
+    // 指定が無ければ stable の最新にする。`latest-<kind>` は新しい場合だけ切り替える
+    let requested = answers
+        .version
+        .as_deref()
+        .is_some_and(|version| version != "latest" && !version.starts_with("latest-"));
+    let answers = Answers {
+        version: answers
+            .version
+            .clone()
+            .or_else(|| Some("latest-stable".to_string())),
+        ..answers.clone()
+    };
+    let version = choose_version(downloader.client(), source, &answers, Some(&installed))?;
+    if installed.is_same_as(&version) || (!requested && !installed.is_older_than(&version)) {
+        println!(
+            "更新はありません（インストール済み: {}）。",
+            installed.label()
+        );
+        return Ok(());
+    }
+
+    let jar_name = jar_filename_from_url(&version.url, &version.version);
+    let checksum = downloader.checksum_for(&version)?;
+    println!();
+    println!("設定サマリ:");
+    println!("- インストール先: {}", install_dir.display());
+    println!(
+        "- バージョン: {} → {}",
+        installed.label(),
+        version.display_label()
+    );
+    print_checksum(&checksum);
+    println!("- 新しい jar: {jar_name}");
+    println!(
+        "- バックアップ先: {}（rollback で戻せます）",
+        Backup::dir(&install_dir).display()
+    );
+    if !prompt_confirm(&answers, "この内容でアップグレードしますか？")? {
+        println!("中断しました。");
+        return Ok(());
+    }
+
+    // 起動スクリプトを書き換えられない場合は、何も変えないうちに止める
+    let scripts = rewrite_start_scripts(&install_dir, &jar_name, None, None)?;
+    if scripts.is_empty() {
+        return Err("start.sh / start.bat が無いため、jar を切り替えられません。".into());
+    }
+    // 検証が済むまでは以前の控えと jar に触れない（同じ名前の jar を上書きする場合もある）
+    let staging_dir = install_dir.join(UPGRADE_STAGING_DIR_NAME);
+    let staged_jar = staging_dir.join(&jar_name);
+    fs::create_dir_all(&staging_dir)?;
+    if let Err(err) = downloader.download_verified(&version, &staged_jar) {
+        let _ = fs::remove_dir_all(&staging_dir);
+        return Err(err);
+    }
+    Backup::create(&install_dir, &installed)?;
+    println!(
+        "現在の jar・起動スクリプト・velocity.toml をバックアップしました: {}",
+        Backup::dir(&install_dir).display()
+    );
+    fs::rename(&staged_jar, install_dir.join(&jar_name))?;
+    fs::remove_dir_all(&staging_dir)?;
+    for (path, contents) in &scripts {
+        write_start_script(path, contents)?;
+    }
+    println!("start.sh / start.bat の jar を {jar_name} に切り替えました。");
+    apply_jar_retention(&install_dir, &jar_name, keep_jars)?;
+    let index_url = answers.jar.is_none().then(|| source.location());
+    record_state(
+        &install_dir,
+        state,
+        &version,
+        &jar_name,
+        index_url,
+        None,
+        None,
+    )?;
+    println!();
+    println!("完了しました。");
+    println!("起動中の場合は Velocity を再起動してください。");
+    Ok(())
+}
+
+/// `[jar] keep`（`--keep-jars`）が設定されていれば、保持数を超えた古い jar を削除する。
+fn apply_jar_retention(
+    install_dir: &Path,
+    current: &str,
+    keep: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    let Some(keep) = keep else {
+        return Ok(());
+    };
+    for path in prune_old_jars(install_dir, current, keep)? {
+        println!("古い jar を削除しました: {}", path.display());
+    }
+    Ok(())
+}
+
+/// `keep` は `[jar] keep`（`--keep-jars`）。未設定なら参照されていない jar をすべて対象にする。
+fn run_prune(args: &[String], keep: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let answers = parse_answers(args)?;
+    let keep = keep.unwrap_or(0);
+    let install_dir = prompt_install_dir(&answers)?;
+    // 参照が分からないまま消すと起動中の jar まで消しうる
+    let referenced = script_jars(&install_dir)?;
+    if referenced.is_empty() {
+        return Err(format!(
+            "起動スクリプト（start.sh / start.bat）から jar を読み取れません: {}",
+            install_dir.display()
+        )
+        .into());
+    }
+    println!("起動スクリプトが参照する jar: {}", referenced.join(", "));
+    let jars: Vec<PathBuf> = unreferenced_jars(&install_dir, None)?
+        .into_iter()
+        .skip(keep)
+        .collect();
+    if jars.is_empty() {
+        println!("削除できる jar はありません。");
+        return Ok(());
+    }
+    println!("参照されていない jar:");
+    for path in &jars {
+        println!(
+            "  - {} ({})",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            format_bytes(fs::metadata(path)?.len())
+        );
+    }
+    if !prompt_confirm(&answers, "これらの jar を削除しますか？")? {
+        println!("中断しました。");
+        return Ok(());
+    }
+    for path in &jars {
+        fs::remove_file(path)?;
+    }
+    println!("{} 個の jar を削除しました。", jars.len());
+    Ok(())
+}
+
+fn run_rollback(answers: &Answers) -> Result<(), Box<dyn Error>> {
+    println!("{} (ロールバック)", binary_name());
+    println!();
+
+    let install_dir = prompt_install_dir(answers)?;
+    let backup = Backup::load(&install_dir)?.ok_or_else(|| {
+        format!(
+            "バックアップがありません（upgrade の実行前の状態に戻せるのは 1 回だけです）: {}",
+            install_dir.display()
+        )
+    })?;
+    let current = InstalledVersion::detect(&install_dir)?;
+    println!();
+    println!("設定サマリ:");
+    println!("- インストール先: {}", install_dir.display());
+    match &current {
+        Some(current) => println!("- バージョン: {} → {}", current.label(), backup.label),
+        None => println!("- バージョン: {}", backup.label),
+    }
+    println!("- 戻す jar: {}", backup.jar);
+    println!("- 戻すファイル: {}", backup.files.join(", "));
+    println!(
+        "- バックアップ日時: {}",
+        format_timestamp(backup.created_at)
+    );
+    if !prompt_confirm(answers, "この内容で戻しますか？")? {
+        println!("中断しました。");
+        return Ok(());
+    }
+    if let Some(before_upgrade) = backup.restore(&install_dir)? {
+        eprintln!(
+            "警告: velocity.toml はアップグレード後に変更されているため戻していません。アップグレード前の内容: {}",
+            before_upgrade.display()
+        );
     }
     println!();
     println!("完了しました。");
+    println!("起動中の場合は Velocity を再起動してください。");
     Ok(())
 }
 
-fn run_deploy(deploy_dir: PathBuf) -> Result<(), Box<dyn Error>> {
+fn run_deploy(
+    deploy_dir: PathBuf,
+    answers: &Answers,
+    save_path: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
     println!("{} (デプロイ)", binary_name());
     println!("Java はインストール済みであることを前提に進めます。");
     println!();
 
-    let install_dir = prompt_deploy_source_dir()?;
+    let install_dir = prompt_deploy_source_dir(answers)?;
     validate_deploy_source(&install_dir)?;
+    let mut recorded = Answers {
+        install_dir: Some(install_dir.clone()),
+        assume_yes: true,
+        ..Answers::default()
+    };
+
+    // 非対話モードで回答が無い場合に、ファイルをコピーする前にエラーにする
+    let toml_src = install_dir.join("velocity.toml");
+    let toml_dest = deploy_dir.join("velocity.toml");
+    let copy_toml = if !toml_src.exists() {
+        false
+    } else if toml_dest.exists() {
+        let overwrite = prompt_yes_no_answer(
+            answers.overwrite,
+            answers.non_interactive,
+            "--overwrite（上書きしない場合は --overwrite=false）",
+            "デプロイ先に velocity.toml があります。上書きしますか？",
+            false,
+        )?;
+        recorded.overwrite = Some(overwrite);
+        overwrite
+    } else {
+        true
+    };
 
     if !deploy_dir.exists() {
         fs::create_dir_all(&deploy_dir)?;
     }
 
-    let script_name = if cfg!(windows) { "start.bat" } else { "start.sh" };
+    let script_name = if cfg!(windows) {
+        "start.bat"
+    } else {
+        "start.sh"
+    };
     let script_src = install_dir.join(script_name);
     if !script_src.exists() {
         return Err(format!("{script_name} が見つかりません。").into());
@@ -240,7 +1033,7 @@ fn run_deploy(deploy_dir: PathBuf) -> Result<(), Box<dyn Error>> {
     }
 
     let script_contents = fs::read_to_string(&script_src)?;
-    let jar_name = extract_jar_from_script(&script_contents)
+    let jar_name = extract_jar_from_script(&script_contents, Shell::from_path(&script_src))
         .ok_or("start スクリプトから jar 名を取得できません。")?;
     let jar_src = if Path::new(&jar_name).is_absolute() {
         PathBuf::from(&jar_name)
@@ -263,160 +1056,407 @@ fn run_deploy(deploy_dir: PathBuf) -> Result<(), Box<dyn Error>> {
     let service_contents = fs::read_to_string(&service_src)?;
     let deploy_abs = absolute_path(&deploy_dir)?;
     let service_updated = update_service_paths(&service_contents, &deploy_abs);
-    fs::write(deploy_dir.join("velocity.service"), service_updated)?;
+    fs::write(deploy_dir.join("velocity.service"), &service_updated)?;
 
-    let toml_src = install_dir.join("velocity.toml");
-    if toml_src.exists() {
-        let toml_dest = deploy_dir.join("velocity.toml");
-        if toml_dest.exists() {
-            let overwrite = prompt_yes_no(
-                "デプロイ先に velocity.toml があります。上書きしますか？",
-                false,
-            )?;
-            if overwrite {
-                fs::copy(&toml_src, &toml_dest)?;
+    if copy_toml {
+        fs::copy(&toml_src, &toml_dest)?;
+    }
+
+    // デプロイ元に記録が無ければ、起動スクリプトと jar から読み取って記録を始める
+    let mut state = match InstallState::load(&install_dir)? {
+        Some(state) => state,
+        None => {
+            let installed = InstalledVersion::detect(&install_dir)?;
+            InstallState {
+                version: installed
+                    .as_ref()
+                    .and_then(|installed| installed.version.clone()),
+                build: installed.and_then(|installed| installed.build),
+                ..InstallState::default()
             }
-        } else {
-            fs::copy(&toml_src, &toml_dest)?;
         }
+    };
+    if state.memory().is_none()
+        && let Some((xms, xmx)) = detect_existing_memory(&deploy_dir)?
+    {
+        state.xms = Some(xms);
+        state.xmx = Some(xmx);
     }
+    state.installer_version = build_version();
+    state.jar = Some(jar_name);
+    state.sha256 = Some(Algorithm::Sha256.digest_file(&jar_dest)?);
+    state.service = Some(read_service(&service_updated));
+    state.deployed_from = Some(absolute_path(&install_dir)?);
+    // デプロイ先としての記録は今回から始まる
+    state.installed_at = unix_now();
+    state.save(&deploy_dir)?;
 
+    save_answers(save_path, &recorded)?;
     println!();
     println!("完了しました。");
     Ok(())
 }
 
-fn download_with_sha256(
+fn choose_version(
     client: &Client,
+    source: &dyn VersionSource,
+    answers: &Answers,
+    installed: Option<&InstalledVersion>,
+) -> Result<VersionInfo, Box<dyn Error>> {
+    if let Some(jar) = &answers.jar {
+        return local_version(jar, answers);
+    }
+    println!(
+        "Velocity のバージョン一覧を取得しています: {}",
+        source.location()
+    );
+    let versions = fetch_versions(client, source)?;
+    let current = installed.and_then(|installed| installed.version.as_deref());
+    Ok(prompt_version(&versions, answers, current)?)
+}
+
+/// インストール先の状態ファイルを読み、記録と異なる箇所があれば警告する。
+fn load_state(install_dir: &Path) -> Result<Option<InstallState>, Box<dyn Error>> {
+    let state = match InstallState::load(install_dir) {
+        Ok(state) => state,
+        Err(err) => {
+            eprintln!("警告: {err}。記録を使わずに続行します。");
+            return Ok(None);
+        }
+    };
+    if let Some(state) = &state {
+        for drift in state.drift(install_dir)? {
+            eprintln!("警告: {drift}（{STATE_FILE_NAME}）");
+        }
+    }
+    Ok(state)
+}
+
+/// 状態ファイルを更新する。`memory` / `service` が `None` の場合は、前回の記録か
+/// インストール先の起動スクリプト・velocity.service から読み取った値を使う。
+fn record_state(
+    install_dir: &Path,
+    previous: Option<InstallState>,
     version: &VersionInfo,
-    dest_path: &Path,
+    jar_name: &str,
+    index_url: Option<&str>,
+    memory: Option<(String, String)>,
+    service: Option<ServiceState>,
 ) -> Result<(), Box<dyn Error>> {
-    let mut response = client.get(&version.url).send()?.error_for_status()?;
-    let mut file = File::create(dest_path)?;
-    let mut hasher = Sha256::new();
-    let mut buffer = [0u8; 8192];
-    loop {
-        let bytes_read = response.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
-        }
-        hasher.update(&buffer[..bytes_read]);
-        file.write_all(&buffer[..bytes_read])?;
+    let mut state = previous.unwrap_or_default();
+    state.installer_version = build_version();
+    state.version = Some(version.version.clone());
+    state.build = version.build;
+    state.jar = Some(jar_name.to_string());
+    state.sha256 = Some(Algorithm::Sha256.digest_file(&install_dir.join(jar_name))?);
+    state.index_url = index_url.map(str::to_string);
+    let memory = match memory.or_else(|| state.memory()) {
+        Some(memory) => Some(memory),
+        None => detect_existing_memory(install_dir)?,
+    };
+    if let Some((xms, xmx)) = memory {
+        state.xms = Some(xms);
+        state.xmx = Some(xmx);
     }
-    let actual = format!("{:x}", hasher.finalize());
-    let expected = version.sha256.to_ascii_lowercase();
-    if actual != expected {
-        let _ = fs::remove_file(dest_path);
-        return Err(format!(
-            "チェックサム不一致: expected={}, actual={}",
-            expected, actual
-        )
-        .into());
+    let service_path = install_dir.join("velocity.service");
+    state.service = match service.or(state.service) {
+        Some(service) => Some(service),
+        None if service_path.exists() => Some(read_service(&fs::read_to_string(service_path)?)),
+        None => None,
+    };
+    state.deployed_from = None;
+    state.save(install_dir)
+}
+
+/// インストール先にある Velocity を調べ、見つかればバージョンを表示する。
+fn detect_installed(install_dir: &Path) -> Result<Option<InstalledVersion>, Box<dyn Error>> {
+    let installed = InstalledVersion::detect(install_dir)?;
+    if let Some(installed) = &installed {
+        println!("インストール済みのバージョン: {}", installed.label());
     }
-    Ok(())
+    Ok(installed)
 }
 
-fn build_client() -> Result<Client, Box<dyn Error>> {
-    Ok(Client::builder()
-        .user_agent("mc-velocity-installer")
-        .build()?)
+/// `--jar` で指定された手元の jar を、一覧の代わりに使うバージョンとして扱う。
+fn local_version(jar: &Path, answers: &Answers) -> Result<VersionInfo, Box<dyn Error>> {
+    if !jar.is_file() {
+        return Err(format!("jar が見つかりません: {}", jar.display()).into());
+    }
+    if answers.checksums.is_empty() {
+        return Err(
+            "--jar を指定する場合は --sha256（または --sha512 / --sha1）も指定してください。"
+                .into(),
+        );
+    }
+    for (algorithm, value) in answers.checksums.iter() {
+        if !algorithm.is_valid_hex(value) {
+            return Err(format!(
+                "--{algorithm} は {} 桁の16進数で指定してください: {value}",
+                algorithm.hex_len()
+            )
+            .into());
+        }
+    }
+    let version = match &answers.version {
+        Some(version) => version.clone(),
+        None => jar
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .ok_or("jar 名を取得できません。")?,
+    };
+    let url = location::file_url(jar)?;
+    Ok(VersionInfo {
+        version,
+        kind: "local".to_string(),
+        url: url.to_string(),
+        checksums: answers.checksums.clone(),
+        build: None,
+    })
+}
+
+fn build_client(config: &HttpConfig) -> Result<Client, Box<dyn Error>> {
+    http::build_client(
+        config,
+        &format!("mc-velocity-installer/{}", build_version()),
+    )
+}
+
+/// `--config` / `MC_VELOCITY_CONFIG` で指定された設定ファイル。
+fn explicit_config_path(args: &[String]) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    Ok(match parse_option_value(args, "--config")? {
+        Some(path) => Some(PathBuf::from(path)),
+        None => std::env::var_os("MC_VELOCITY_CONFIG")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from),
+    })
+}
+
+/// 読み込む設定ファイル（指定が無ければ既定の場所）。
+fn config_file_path(args: &[String]) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    Ok(explicit_config_path(args)?.or_else(Config::default_path))
+}
+
+/// 設定ファイル < 環境変数 < フラグの順に優先して設定を決める。
+fn load_config(args: &[String]) -> Result<Config, Box<dyn Error>> {
+    let file = match explicit_config_path(args)? {
+        Some(path) => {
+            if !path.exists() {
+                return Err(format!("設定ファイルが見つかりません: {}", path.display()).into());
+            }
+            Config::load(&path)?
+        }
+        None => match Config::default_path() {
+            Some(path) => Config::load(&path)?,
+            None => Config::default(),
+        },
+    };
+    let seconds = |name: &str| -> Result<Option<u64>, Box<dyn Error>> {
+        parse_option_value(args, name)?
+            .map(|value| parse_seconds(name, &value))
+            .transpose()
+    };
+    let http_flags = HttpConfig {
+        connect_timeout: seconds("--connect-timeout")?,
+        timeout: seconds("--timeout")?,
+        proxy: parse_option_value(args, "--proxy")?,
+        no_proxy: parse_option_value(args, "--no-proxy")?,
+        ca_bundle: parse_option_value(args, "--ca-bundle")?.map(PathBuf::from),
+        user_agent: parse_option_value(args, "--user-agent")?,
+    };
+    let cache_flags = CacheConfig {
+        dir: parse_option_value(args, "--cache-dir")?.map(PathBuf::from),
+        enabled: has_flag(args, "--no-cache").then_some(false),
+    };
+    Ok(Config {
+        http: file
+            .http
+            .overlay(HttpConfig::from_env()?)
+            .overlay(http_flags),
+        mirrors: file.mirrors.overlay(MirrorConfig::from_env()),
+        cache: file
+            .cache
+            .overlay(CacheConfig::from_env())
+            .overlay(cache_flags),
+        index: file
+            .index
+            .overlay(IndexConfig::from_env())
+            .overlay(IndexConfig {
+                skip_invalid_entries: has_flag(args, "--skip-invalid-entries").then_some(true),
+            }),
+        checksum: file
+            .checksum
+            .overlay(ChecksumConfig::from_env())
+            .overlay(ChecksumConfig {
+                allow_weak: has_flag(args, "--allow-weak-checksum").then_some(true),
+            }),
+        signature: file
+            .signature
+            .overlay(SignatureConfig::from_env())
+            .overlay(SignatureConfig {
+                keys: None,
+                required: has_flag(args, "--require-signature").then_some(true),
+            }),
+        jar: file.jar.overlay(JarConfig::from_env()?).overlay(JarConfig {
+            manifest_check: parse_option_value(args, "--jar-manifest-check")?
+                .map(|value| ManifestCheck::parse(&value))
+                .transpose()?,
+            keep: parse_option_value(args, "--keep-jars")?
+                .map(|value| parse_count("--keep-jars", &value))
+                .transpose()?,
+        }),
+    })
 }
 
-fn write_start_scripts(
+/// キャッシュの置き場所。`--cache-dir` などが無ければ XDG のキャッシュディレクトリ。
+fn cache_dir(config: &CacheConfig) -> Option<PathBuf> {
+    config.dir.clone().or_else(default_cache_dir)
+}
+
+/// キャッシュを使う設定なら、その置き場所。
+fn enabled_cache_dir(config: &CacheConfig) -> Option<PathBuf> {
+    cache_dir(config).filter(|_| config.enabled.unwrap_or(true))
+}
+
+fn write_start_scripts(install_dir: &Path, command: &JavaCommand) -> Result<(), Box<dyn Error>> {
+    write_start_script(&install_dir.join("start.sh"), &command.render_sh())?;
+    write_start_script(&install_dir.join("start.bat"), &command.render_bat())
+}
+
+/// start.sh / start.bat の java の行を、jar と（指定があれば）メモリだけ変えて書き直す。
+///
+/// どちらのスクリプトも無い場合は何も書かずに `false`。詳細は [`rewrite_start_scripts`]。
+fn regenerate_start_scripts(
     install_dir: &Path,
-    xms: &str,
-    xmx: &str,
     jar_name: &str,
-) -> Result<(), Box<dyn Error>> {
-    let sh_path = install_dir.join("start.sh");
-    let bat_path = install_dir.join("start.bat");
-
-    let sh_contents = format!(
-        "#!/usr/bin/env sh\nset -e\nDIR=\"$(cd \"$(dirname \"$0\")\" && pwd)\"\ncd \"$DIR\"\nexec java -Xms{} -Xmx{} -jar \"{}\"\n",
-        xms, xmx, jar_name
-    );
-    fs::write(&sh_path, sh_contents)?;
+    memory: Option<(&str, &str)>,
+    missing_memory: Option<(&str, &str)>,
+) -> Result<bool, Box<dyn Error>> {
+    let rewritten = rewrite_start_scripts(install_dir, jar_name, memory, missing_memory)?;
+    for (path, contents) in &rewritten {
+        write_start_script(path, contents)?;
+    }
+    Ok(!rewritten.is_empty())
+}
 
-    let bat_contents = format!(
-        "@echo off\r\nset \"DIR=%~dp0\"\r\ncd /d \"%DIR%\"\r\njava -Xms{} -Xmx{} -jar \"{}\"\r\n",
-        xms, xmx, jar_name
-    );
-    fs::write(&bat_path, bat_contents)?;
+/// 書き直した start.sh / start.bat の内容（書き込みはしない）。無いスクリプトは含めない。
+///
+/// `missing_memory` は `-Xms` / `-Xmx` が書かれていないスクリプトにだけ使う。
+/// JVM オプションやプログラム引数、ほかの行はそのまま残す。java の行を読み取れない、または jar を
+/// 書き換えられないスクリプトがあればエラー。
+fn rewrite_start_scripts(
+    install_dir: &Path,
+    jar_name: &str,
+    memory: Option<(&str, &str)>,
+    missing_memory: Option<(&str, &str)>,
+) -> Result<Vec<(PathBuf, String)>, Box<dyn Error>> {
+    let mut rewritten = Vec::new();
+    for name in ["start.sh", "start.bat"] {
+        let path = install_dir.join(name);
+        if !path.exists() {
+            continue;
+        }
+        let script = StartScript::parse(&fs::read_to_string(&path)?, Shell::from_path(&path))
+            .ok_or_else(|| {
+                format!(
+                    "{name} から java -jar の行を読み取れないため、起動スクリプトを書き換えません。"
+                )
+            })?;
+        let memory = memory.or_else(|| {
+            script
+                .command()
+                .memory()
+                .is_none()
+                .then_some(missing_memory)
+                .flatten()
+        });
+        let contents = script
+            .render(jar_name, memory)
+            .map_err(|err| format!("{name}: {err}"))?;
+        rewritten.push((path, contents));
+    }
+    Ok(rewritten)
+}
 
+fn write_start_script(path: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, contents)?;
     #[cfg(unix)]
-    {
+    if path.extension().is_some_and(|extension| extension == "sh") {
         use std::os::unix::fs::PermissionsExt;
-        let mut permissions = fs::metadata(&sh_path)?.permissions();
+        let mut permissions = fs::metadata(path)?.permissions();
         permissions.set_mode(0o755);
-        fs::set_permissions(&sh_path, permissions)?;
+        fs::set_permissions(path, permissions)?;
     }
-
     Ok(())
 }
 
-fn jar_filename_from_url(url: &str, version: &str) -> String {
-    if let Ok(parsed) = Url::parse(url) {
-        if let Some(name) = parsed.path_segments().and_then(|segments| segments.last()) {
-            if !name.is_empty() {
-                return name.to_string();
-            }
-        }
+fn parse_answers(args: &[String]) -> Result<Answers, Box<dyn Error>> {
+    let from_flags = Answers {
+        install_dir: parse_option_value(args, "--dir")?.map(PathBuf::from),
+        version: parse_option_value(args, "--version")?,
+        checksums: Checksums {
+            sha512: parse_option_value(args, "--sha512")?,
+            sha256: parse_option_value(args, "--sha256")?,
+            sha1: parse_option_value(args, "--sha1")?,
+        },
+        jar: parse_option_value(args, "--jar")?.map(PathBuf::from),
+        xms: parse_option_value(args, "--xms")?,
+        xmx: parse_option_value(args, "--xmx")?,
+        overwrite: parse_bool_flag(args, "--overwrite")?,
+        replace_scripts: parse_bool_flag(args, "--replace-scripts")?,
+        update_schedule: parse_option_value(args, "--update-schedule")?,
+        update_kind: parse_option_value(args, "--update-kind")?,
+        assume_yes: has_flag(args, "--yes") || has_flag(args, "-y"),
+        non_interactive: has_flag(args, "--non-interactive"),
+        ..Answers::default()
+    };
+    match parse_option_value(args, "--answers")? {
+        Some(path) => Ok(Answers::load(Path::new(&path))?.overlay(from_flags)),
+        None => Ok(from_flags),
     }
-    format!("velocity-{}.jar", version)
 }
 
-fn detect_existing_memory(install_dir: &Path) -> Result<Option<(String, String)>, Box<dyn Error>> {
-    let sh_path = install_dir.join("start.sh");
-    if let Some(values) = read_memory_from_script(&sh_path)? {
-        return Ok(Some(values));
+fn save_answers(path: Option<&Path>, recorded: &Answers) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = path {
+        recorded.save(path)?;
+        println!("回答を保存しました: {}", path.display());
     }
-    let bat_path = install_dir.join("start.bat");
-    if let Some(values) = read_memory_from_script(&bat_path)? {
-        return Ok(Some(values));
-    }
-    Ok(None)
+    Ok(())
 }
 
-fn read_memory_from_script(path: &Path) -> Result<Option<(String, String)>, Box<dyn Error>> {
-    if !path.exists() {
-        return Ok(None);
+// `--version` は値が続く場合のみ Velocity のバージョン指定として扱う。
+// 値の無い `--version` は単独で指定した場合だけビルドのバージョン表示とし、
+// ほかのフラグと組み合わせた場合は値の指定漏れとしてエラーにする。
+fn wants_build_version(args: &[String]) -> Result<bool, Box<dyn Error>> {
+    if args.iter().any(|arg| arg == "-V") {
+        return Ok(true);
+    }
+    let bare = args.iter().enumerate().any(|(idx, arg)| {
+        arg == "--version" && args.get(idx + 1).is_none_or(|next| next.starts_with('-'))
+    });
+    if !bare {
+        return Ok(false);
     }
-    let contents = fs::read_to_string(path)?;
-    Ok(extract_memory_flags(&contents))
+    if args.len() == 1 {
+        return Ok(true);
+    }
+    Err("--version の値が必要です（ビルドのバージョンは --version / -V だけを指定して表示します）。".into())
 }
 
-fn extract_memory_flags(contents: &str) -> Option<(String, String)> {
-    let mut xms: Option<String> = None;
-    let mut xmx: Option<String> = None;
-    let mut iter = contents.split_whitespace().peekable();
-    while let Some(token) = iter.next() {
-        if token == "-Xms" {
-            if let Some(value) = iter.next() {
-                xms = Some(value.trim_matches('"').to_string());
-            }
-            continue;
-        }
-        if token == "-Xmx" {
-            if let Some(value) = iter.next() {
-                xmx = Some(value.trim_matches('"').to_string());
-            }
-            continue;
-        }
-        if let Some(value) = token.strip_prefix("-Xms") {
-            if !value.is_empty() {
-                xms = Some(value.trim_matches('"').to_string());
-            }
-            continue;
-        }
-        if let Some(value) = token.strip_prefix("-Xmx") {
-            if !value.is_empty() {
-                xmx = Some(value.trim_matches('"').to_string());
-            }
-        }
+fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|arg| arg == name)
+}
+
+/// `--name` は `true`、`--name=true` / `--name=false` はその値。指定が無ければ `None`。
+fn parse_bool_flag(args: &[String], name: &str) -> Result<Option<bool>, Box<dyn Error>> {
+    if has_flag(args, name) {
+        return Ok(Some(true));
     }
-    match (xms, xmx) {
-        (Some(xms), Some(xmx)) => Some((xms, xmx)),
-        _ => None,
+    let prefix = format!("{name}=");
+    match args.iter().find_map(|arg| arg.strip_prefix(&prefix)) {
+        Some("true") => Ok(Some(true)),
+        Some("false") => Ok(Some(false)),
+        Some(value) => Err(format!("{name} には true か false を指定してください: {value}").into()),
+        None => Ok(None),
     }
 }
 
@@ -442,7 +1482,7 @@ fn parse_option_value(args: &[String], name: &str) -> Result<Option<String>, Box
     Ok(None)
 }
 
-fn write_systemd_service(settings: &InstallSettings) -> Result<(), Box<dyn Error>> {
+fn write_systemd_service(settings: &InstallSettings) -> Result<ServiceState, Box<dyn Error>> {
     let service_path = settings.install_dir.join("velocity.service");
     let install_dir = absolute_path(&settings.install_dir)?;
 
@@ -458,9 +1498,161 @@ fn write_systemd_service(settings: &InstallSettings) -> Result<(), Box<dyn Error
         group
     );
     fs::write(service_path, contents)?;
+    Ok(ServiceState {
+        working_directory: install_dir,
+        exec_start,
+        user,
+        group,
+    })
+}
+
+/// 自動更新のコマンドに、インストール時と同じ取得元・設定を使わせるためのフラグ。
+///
+/// 読み込んだ設定ファイルは `--config` で絶対パスにして渡す（サービスのユーザーでは既定の場所が変わるため）。
+fn update_options(args: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut options = Vec::new();
+    if let Some(path) = config_file_path(args)?.filter(|path| path.exists()) {
+        options.push("--config".to_string());
+        options.push(absolute_path(&path)?.display().to_string());
+    }
+    for flag in UPDATE_VALUE_FLAGS {
+        if let Some(value) = parse_option_value(args, flag)? {
+            let value = match flag {
+                "--ca-bundle" | "--cache-dir" => {
+                    absolute_path(Path::new(&value))?.display().to_string()
+                }
+                _ => value,
+            };
+            options.push(flag.to_string());
+            options.push(value);
+        }
+    }
+    for flag in UPDATE_SWITCH_FLAGS {
+        if has_flag(args, flag) {
+            options.push(flag.to_string());
+        }
+    }
+    Ok(options)
+}
+
+/// 自動更新の `velocity-update.service` / `velocity-update.timer` を生成する。
+///
+/// `check-updates` で更新がある場合だけ `upgrade` を実行し、成功したら `velocity.service` を再起動する。
+/// インストール時の `MC_VELOCITY_*` 環境変数と `options` を引き継ぎ、同じ取得元・検証の設定で更新する。
+fn write_update_timer(
+    install_dir: &Path,
+    service: &ServiceState,
+    timer: &UpdateTimer,
+    index_url: &str,
+    options: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let installer = std::env::current_exe()?.display().to_string();
+    let working_directory = service.working_directory.display().to_string();
+    let mut environment: Vec<(String, String)> = std::env::vars()
+        .filter(|(name, _)| {
+            name.starts_with("MC_VELOCITY_")
+                && name != "MC_VELOCITY_CONFIG"
+                && name != "MC_VELOCITY_INDEX_URL"
+        })
+        .collect();
+    environment.push(("MC_VELOCITY_INDEX_URL".to_string(), index_url.to_string()));
+    environment.sort();
+    let environment = environment
+        .iter()
+        .map(|(name, value)| {
+            Ok(format!(
+                "Environment={}\n",
+                systemd_string(&format!("{name}={value}"))?
+            ))
+        })
+        .collect::<Result<String, Box<dyn Error>>>()?;
+
+    let kind = timer.kind.as_str();
+    let check = [
+        installer.as_str(),
+        "check-updates",
+        "--dir",
+        &working_directory,
+        "--kind",
+        kind,
+    ]
+    .into_iter()
+    .chain(options.iter().map(String::as_str))
+    .map(sh_quote)
+    .collect::<Vec<_>>()
+    .join(" ");
+    let condition = format!("{check}; case $? in 2) exit 0 ;; 0) exit 1 ;; *) exit 255 ;; esac");
+    let version = format!("latest-{kind}");
+    let upgrade = [
+        installer.as_str(),
+        "upgrade",
+        "--non-interactive",
+        "--yes",
+        "--quiet",
+        "--dir",
+        &working_directory,
+        "--version",
+        &version,
+    ]
+    .into_iter()
+    .chain(options.iter().map(String::as_str))
+    .map(systemd_quote)
+    .collect::<Result<Vec<_>, _>>()?
+    .join(" ");
+    let service_contents = format!(
+        "[Unit]\nDescription=Velocity Minecraft Proxy update\nWants=network-online.target\nAfter=network-online.target\n\n[Service]\nType=oneshot\nWorkingDirectory={working_directory}\n{environment}ExecCondition=/bin/sh -c {condition}\nExecStart={upgrade}\nExecStartPost=+/bin/systemctl try-restart velocity.service\nUser={user}\nGroup={group}\n",
+        condition = systemd_quote(&condition)?,
+        user = service.user,
+        group = service.group,
+    );
+    fs::write(
+        install_dir.join("velocity-update.service"),
+        service_contents,
+    )?;
+    let timer_contents = format!(
+        "[Unit]\nDescription=Velocity Minecraft Proxy update timer\n\n[Timer]\nOnCalendar={}\nPersistent=true\nRandomizedDelaySec=1h\n\n[Install]\nWantedBy=timers.target\n",
+        timer.schedule
+    );
+    fs::write(install_dir.join("velocity-update.timer"), timer_contents)?;
     Ok(())
 }
 
+/// systemd の `Exec…=` の 1 つの引数として書ける形にする（`$` / `%` も展開させない）。
+fn systemd_quote(value: &str) -> Result<String, Box<dyn Error>> {
+    if is_plain_argument(value) {
+        return Ok(value.to_string());
+    }
+    Ok(systemd_string(value)?.replace('$', "$$"))
+}
+
+/// systemd のユニットファイルに引用符で囲んで書ける形にする（`%` を展開させない）。
+fn systemd_string(value: &str) -> Result<String, Box<dyn Error>> {
+    if value.chars().any(char::is_control) {
+        return Err(format!("ユニットファイルに改行などの制御文字は書けません: {value:?}").into());
+    }
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%");
+    Ok(format!("\"{escaped}\""))
+}
+
+/// 引用符で囲まなくても sh / systemd で 1 つの引数のまま読まれる値か。
+fn is_plain_argument(value: &str) -> bool {
+    !value.is_empty()
+        && value.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/' | ':' | '=' | ',')
+        })
+}
+
+/// sh の 1 つの引数として書ける形にする。
+fn sh_quote(value: &str) -> String {
+    if is_plain_argument(value) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
 fn absolute_path(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
     if path.is_absolute() {
         Ok(path.to_path_buf())
@@ -479,24 +1671,6 @@ fn validate_deploy_source(path: &Path) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn extract_jar_from_script(contents: &str) -> Option<String> {
-    let mut iter = contents.split_whitespace();
-    while let Some(token) = iter.next() {
-        if token == "-jar" {
-            if let Some(value) = iter.next() {
-                return Some(value.trim_matches('"').to_string());
-            }
-            continue;
-        }
-        if let Some(value) = token.strip_prefix("-jar") {
-            if !value.is_empty() {
-                return Some(value.trim_matches('"').to_string());
-            }
-        }
-    }
-    None
-}
-
 fn update_service_paths(contents: &str, deploy_dir: &Path) -> String {
     let mut lines = Vec::new();
     for line in contents.lines() {
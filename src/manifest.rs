@@ -0,0 +1,192 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::progress::ProgressStyle;
+use crate::version::VersionInfo;
+
+/// Velocity の jar の `Main-Class`。
+pub const VELOCITY_MAIN_CLASS: &str = "com.velocitypowered.proxy.Velocity";
+
+const MANIFEST_PATH: &str = "META-INF/MANIFEST.MF";
+
+/// jar の中身が選んだバージョンと食い違う場合の扱い。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestCheck {
+    Off,
+    /// 警告を表示して続行する（既定）。
+    Warn,
+    /// エラーにして配置しない。
+    Fail,
+}
+
+impl ManifestCheck {
+    pub fn parse(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "fail" => Ok(Self::Fail),
+            _ => Err(format!("jar の確認方法が不明です: {name}（warn / fail / off）").into()),
+        }
+    }
+}
+
+/// `META-INF/MANIFEST.MF` のうち確認に使う項目。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JarManifest {
+    pub main_class: Option<String>,
+    pub implementation_version: Option<String>,
+}
+
+impl JarManifest {
+    /// jar（zip）を開いて `META-INF/MANIFEST.MF` を読む。
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut archive = zip::ZipArchive::new(File::open(path)?)
+            .map_err(|err| format!("jar（zip）として開けません ({err})"))?;
+        let mut entry = archive
+            .by_name(MANIFEST_PATH)
+            .map_err(|_| format!("{MANIFEST_PATH} がありません"))?;
+        let mut contents = String::new();
+        entry
+            .read_to_string(&mut contents)
+            .map_err(|err| format!("{MANIFEST_PATH} を読み込めません ({err})"))?;
+        Ok(Self::parse(&contents))
+    }
+
+    /// メインセクションの属性を読む。72 バイトで折り返した行（先頭が空白）は前の行に続ける。
+    pub fn parse(contents: &str) -> Self {
+        let mut attributes: Vec<(String, String)> = Vec::new();
+        for line in contents.lines() {
+            if line.is_empty() {
+                // 空行以降は各エントリのセクション
+                break;
+            }
+            if let Some(continuation) = line.strip_prefix(' ') {
+                if let Some((_, value)) = attributes.last_mut() {
+                    value.push_str(continuation);
+                }
+                continue;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                attributes.push((name.trim().to_string(), value.trim_start().to_string()));
+            }
+        }
+        let get = |name: &str| {
+            attributes
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.trim_end().to_string())
+        };
+        Self {
+            main_class: get("Main-Class"),
+            implementation_version: get("Implementation-Version"),
+        }
+    }
+
+    /// Velocity の jar で、`version` と同じバージョンかを確かめて、食い違いをすべて返す。
+    ///
+    /// `Implementation-Version` は `3.4.0-SNAPSHOT (git-xxxxxxxx-b500)` の形なので、
+    /// 最初の空白までを比べる。手元の jar（`--jar`）はバージョン名が任意なので比べない。
+    pub fn problems(&self, version: &VersionInfo) -> Vec<String> {
+        let mut problems = Vec::new();
+        match self.main_class.as_deref() {
+            Some(VELOCITY_MAIN_CLASS) => {}
+            Some(main_class) => problems.push(format!(
+                "Main-Class が Velocity のものではありません: {main_class}"
+            )),
+            None => problems.push("Main-Class がありません".to_string()),
+        }
+        if version.kind == "local" {
+            return problems;
+        }
+        match self.implementation_version.as_deref() {
+            Some(actual)
+                if actual
+                    .split_whitespace()
+                    .next()
+                    .is_some_and(|actual| actual.eq_ignore_ascii_case(&version.version)) => {}
+            Some(actual) => problems.push(format!(
+                "Implementation-Version が選択したバージョンと一致しません: expected={}, actual={actual}",
+                version.version
+            )),
+            None => problems.push("Implementation-Version がありません".to_string()),
+        }
+        problems
+    }
+}
+
+/// 取得した jar の中身を確かめる。`Fail` の場合は食い違いがあればエラーにする。
+pub fn check_jar(
+    path: &Path,
+    version: &VersionInfo,
+    check: ManifestCheck,
+    progress: ProgressStyle,
+) -> Result<(), Box<dyn Error>> {
+    if check == ManifestCheck::Off {
+        return Ok(());
+    }
+    let problems = match JarManifest::read(path) {
+        Ok(manifest) => {
+            if let Some(actual) = &manifest.implementation_version {
+                progress.status(&format!("jar のバージョン: {actual}"));
+            }
+            manifest.problems(version)
+        }
+        Err(err) => vec![err.to_string()],
+    };
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let summary = format!(
+        "jar の中身が {} と一致しません:\n  - {}",
+        version.version,
+        problems.join("\n  - ")
+    );
+    if check == ManifestCheck::Fail {
+        return Err(summary.into());
+    }
+    eprintln!("警告: {summary}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::Checksums;
+
+    fn version(version: &str) -> VersionInfo {
+        VersionInfo {
+            version: version.to_string(),
+            kind: "stable".to_string(),
+            url: "https://example.com/velocity.jar".to_string(),
+            checksums: Checksums::default(),
+            build: None,
+        }
+    }
+
+    #[test]
+    fn parse_reads_main_section_with_continuation_lines() {
+        let manifest = JarManifest::parse(
+            "Manifest-Version: 1.0\r\nMain-Class: com.velocitypowered.proxy.Velo\r\n city\r\nImplementation-Version: 3.4.0-SNAPSHOT (git-abc\r\n def-b500)\r\n\r\nName: other\r\nImplementation-Version: 9.9.9\r\n",
+        );
+        assert_eq!(manifest.main_class.as_deref(), Some(VELOCITY_MAIN_CLASS));
+        assert_eq!(
+            manifest.implementation_version.as_deref(),
+            Some("3.4.0-SNAPSHOT (git-abcdef-b500)")
+        );
+        assert!(manifest.problems(&version("3.4.0-SNAPSHOT")).is_empty());
+    }
+
+    #[test]
+    fn problems_reports_wrong_artifact() {
+        let manifest = JarManifest {
+            main_class: Some("io.papermc.paperclip.Main".to_string()),
+            implementation_version: Some("1.21.4".to_string()),
+        };
+        let problems = manifest.problems(&version("3.4.0"));
+        assert_eq!(problems.len(), 2, "{problems:?}");
+        assert!(problems[0].contains("io.papermc.paperclip.Main"));
+        assert!(problems[1].contains("expected=3.4.0, actual=1.21.4"));
+    }
+}
@@ -0,0 +1,177 @@
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+const BAR_INTERVAL: Duration = Duration::from_millis(200);
+const LINE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// ダウンロード進捗の表示方法。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressStyle {
+    /// 端末向け。同じ行を書き換える。
+    Bar,
+    /// 端末以外向け。一定間隔で 1 行ずつ出力する。
+    Lines,
+    /// 表示しない。
+    Quiet,
+}
+
+impl ProgressStyle {
+    pub fn detect(quiet: bool) -> Self {
+        if quiet {
+            Self::Quiet
+        } else if io::stdout().is_terminal() {
+            Self::Bar
+        } else {
+            Self::Lines
+        }
+    }
+
+    /// 取得の状況（取得元・キャッシュの利用など）を 1 行表示する。`Quiet` では表示しない。
+    pub fn status(self, message: &str) {
+        if self != Self::Quiet {
+            println!("{message}");
+        }
+    }
+}
+
+/// 1 回分の取得の進捗。`offset` は再開時に取得済みだったバイト数。
+pub struct Progress {
+    style: ProgressStyle,
+    total: Option<u64>,
+    offset: u64,
+    downloaded: u64,
+    started: Instant,
+    last_report: Instant,
+}
+
+impl Progress {
+    pub fn new(style: ProgressStyle, offset: u64, total: Option<u64>) -> Self {
+        let now = Instant::now();
+        Self {
+            style,
+            total,
+            offset,
+            downloaded: 0,
+            started: now,
+            last_report: now,
+        }
+    }
+
+    pub fn advance(&mut self, bytes: u64) {
+        self.downloaded += bytes;
+        let interval = match self.style {
+            ProgressStyle::Bar => BAR_INTERVAL,
+            ProgressStyle::Lines => LINE_INTERVAL,
+            ProgressStyle::Quiet => return,
+        };
+        if self.last_report.elapsed() >= interval {
+            self.last_report = Instant::now();
+            self.report();
+        }
+    }
+
+    pub fn finish(&mut self) {
+        match self.style {
+            ProgressStyle::Bar => {
+                self.report();
+                println!();
+            }
+            ProgressStyle::Lines => self.report(),
+            ProgressStyle::Quiet => {}
+        }
+    }
+
+    fn report(&self) {
+        let line = format_status(
+            self.offset + self.downloaded,
+            self.total,
+            self.downloaded,
+            self.started.elapsed(),
+        );
+        if self.style == ProgressStyle::Bar {
+            print!("\r\x1b[2K  {line}");
+            let _ = io::stdout().flush();
+        } else {
+            println!("  {line}");
+        }
+    }
+}
+
+/// `12.0 MiB / 40.0 MiB (30%)  2.0 MiB/s  残り 14s` の形式。
+/// 速度と残り時間は今回取得した分（`session_bytes`）から計算する。
+fn format_status(
+    current: u64,
+    total: Option<u64>,
+    session_bytes: u64,
+    elapsed: Duration,
+) -> String {
+    let seconds = elapsed.as_secs_f64();
+    let rate = if seconds > 0.0 {
+        session_bytes as f64 / seconds
+    } else {
+        0.0
+    };
+    let mut line = match total {
+        Some(total) if total > 0 => format!(
+            "{} / {} ({}%)",
+            format_bytes(current),
+            format_bytes(total),
+            current.min(total) * 100 / total
+        ),
+        _ => format_bytes(current),
+    };
+    line.push_str(&format!("  {}/s", format_bytes(rate as u64)));
+    if let Some(total) = total
+        && rate > 0.0
+    {
+        let remaining = total.saturating_sub(current) as f64 / rate;
+        line.push_str(&format!("  残り {}", format_duration(remaining as u64)));
+    }
+    line
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn format_duration(seconds: u64) -> String {
+    if seconds >= 3600 {
+        format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60)
+    } else if seconds >= 60 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_status_includes_percentage_speed_and_eta() {
+        let line = format_status(
+            12 * 1024 * 1024,
+            Some(40 * 1024 * 1024),
+            8 * 1024 * 1024,
+            Duration::from_secs(4),
+        );
+        assert_eq!(line, "12.0 MiB / 40.0 MiB (30%)  2.0 MiB/s  残り 14s");
+    }
+
+    #[test]
+    fn format_status_without_content_length() {
+        let line = format_status(512, None, 512, Duration::from_secs(1));
+        assert_eq!(line, "512 B  512 B/s");
+    }
+}
@@ -0,0 +1,97 @@
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::checksum::Checksums;
+
+/// 対話で尋ねる内容に対して、あらかじめ与えられた回答。
+///
+/// CLI フラグや回答ファイルから組み立て、各プロンプト関数に渡す。
+/// 値が無い項目だけを対話で尋ね、非対話モードではエラーにする。
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Answers {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) install_dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) version: Option<String>,
+    /// 記録時に選択したバージョン（または `--sha256` など）の build / チェックサム。一致を検証する。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) build: Option<u64>,
+    #[serde(flatten)]
+    pub(crate) checksums: Checksums,
+    /// ダウンロードせずに使う手元の jar。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) jar: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) xms: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) xmx: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) overwrite: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) replace_scripts: Option<bool>,
+    /// 自動更新のタイマーの実行スケジュール（systemd の `OnCalendar`）。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) update_schedule: Option<String>,
+    /// 自動更新で追う type。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) update_kind: Option<String>,
+    #[serde(rename = "yes")]
+    pub(crate) assume_yes: bool,
+    #[serde(skip)]
+    pub(crate) non_interactive: bool,
+}
+
+impl Answers {
+    pub(crate) fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("回答ファイルを読み込めません: {} ({err})", path.display()))?;
+        let answers = serde_json::from_str(&contents)
+            .map_err(|err| format!("回答ファイルの形式が不正です: {} ({err})", path.display()))?;
+        Ok(answers)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut contents = serde_json::to_string_pretty(self)?;
+        contents.push('\n');
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// `other` に値がある項目はそちらを優先して上書きする。
+    ///
+    /// バージョンを上書きした場合、元の build / チェックサムは別バージョンのものなので引き継がない。
+    pub(crate) fn overlay(self, other: Answers) -> Answers {
+        let (build, checksums) = if other.version.is_some() {
+            (other.build, other.checksums)
+        } else {
+            (
+                other.build.or(self.build),
+                self.checksums.overlay(other.checksums),
+            )
+        };
+        Answers {
+            install_dir: other.install_dir.or(self.install_dir),
+            version: other.version.or(self.version),
+            build,
+            checksums,
+            jar: other.jar.or(self.jar),
+            xms: other.xms.or(self.xms),
+            xmx: other.xmx.or(self.xmx),
+            overwrite: other.overwrite.or(self.overwrite),
+            replace_scripts: other.replace_scripts.or(self.replace_scripts),
+            update_schedule: other.update_schedule.or(self.update_schedule),
+            update_kind: other.update_kind.or(self.update_kind),
+            assume_yes: other.assume_yes || self.assume_yes,
+            non_interactive: other.non_interactive || self.non_interactive,
+        }
+    }
+}
+
+pub(crate) fn missing_answer(flag: &str) -> io::Error {
+    io::Error::other(format!("非対話モードでは {flag} の指定が必要です。"))
+}
@@ -1,5 +1,7 @@
 use std::io::{self, Write};
 
+use super::answers::{Answers, missing_answer};
+
 pub(crate) fn prompt_with_default(message: &str, default: &str) -> io::Result<String> {
     let input = prompt_line(&format!("{message} [{default}]: "))?;
     if input.trim().is_empty() {
@@ -9,31 +11,6 @@ pub(crate) fn prompt_with_default(message: &str, default: &str) -> io::Result<St
     }
 }
 
-pub(crate) fn prompt_usize_with_default(
-    message: &str,
-    default: usize,
-    range: std::ops::RangeInclusive<usize>,
-) -> io::Result<usize> {
-    loop {
-        let input = prompt_line(&format!("{message} [{default}]: "))?;
-        let value = if input.trim().is_empty() {
-            default
-        } else {
-            match input.trim().parse::<usize>() {
-                Ok(value) => value,
-                Err(_) => {
-                    println!("数値を入力してください。");
-                    continue;
-                }
-            }
-        };
-        if range.contains(&value) {
-            return Ok(value);
-        }
-        println!("範囲内の番号を入力してください。");
-    }
-}
-
 pub(crate) fn prompt_yes_no(message: &str, default: bool) -> io::Result<bool> {
     let suffix = if default { "[Y/n]" } else { "[y/N]" };
     loop {
@@ -57,3 +34,28 @@ pub(crate) fn prompt_line(message: &str) -> io::Result<String> {
     io::stdin().read_line(&mut input)?;
     Ok(input.trim().to_string())
 }
+
+pub(crate) fn prompt_confirm(answers: &Answers, message: &str) -> io::Result<bool> {
+    if answers.assume_yes {
+        return Ok(true);
+    }
+    if answers.non_interactive {
+        return Err(missing_answer("--yes"));
+    }
+    prompt_yes_no(message, true)
+}
+
+/// `flag` は非対話モードで回答が無い場合にエラーで案内するフラグ。
+pub(crate) fn prompt_yes_no_answer(
+    answer: Option<bool>,
+    non_interactive: bool,
+    flag: &str,
+    message: &str,
+    default: bool,
+) -> io::Result<bool> {
+    match answer {
+        Some(value) => Ok(value),
+        None if non_interactive => Err(missing_answer(flag)),
+        None => prompt_yes_no(message, default),
+    }
+}
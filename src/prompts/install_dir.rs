@@ -3,41 +3,36 @@ use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 
-use super::input::{prompt_with_default, prompt_yes_no};
+use super::answers::{Answers, missing_answer};
+use super::input::{prompt_confirm, prompt_with_default, prompt_yes_no};
 
-pub(crate) fn prompt_install_dir() -> io::Result<PathBuf> {
-    let default_dir = default_install_dir();
-    let default_display = default_dir.to_string_lossy();
-    loop {
-        let input = prompt_with_default("インストール先ディレクトリ", &default_display)?;
-        let path = PathBuf::from(input);
-        if path.as_os_str().is_empty() {
-            println!("空のパスは指定できません。");
-            continue;
-        }
-        let confirm = prompt_yes_no(
-            &format!("インストール先は {} でよいですか？", path.display()),
-            true,
-        )?;
-        if confirm {
-            return Ok(path);
-        }
-    }
+pub(crate) fn prompt_install_dir(answers: &Answers) -> io::Result<PathBuf> {
+    prompt_dir(answers, "インストール先")
 }
 
-pub(crate) fn prompt_deploy_source_dir() -> io::Result<PathBuf> {
+pub(crate) fn prompt_deploy_source_dir(answers: &Answers) -> io::Result<PathBuf> {
+    prompt_dir(answers, "デプロイ元")
+}
+
+fn prompt_dir(answers: &Answers, label: &str) -> io::Result<PathBuf> {
+    if let Some(path) = &answers.install_dir {
+        return Ok(path.clone());
+    }
+    if answers.non_interactive {
+        return Err(missing_answer("--dir"));
+    }
     let default_dir = default_install_dir();
     let default_display = default_dir.to_string_lossy();
     loop {
-        let input = prompt_with_default("デプロイ元ディレクトリ", &default_display)?;
+        let input = prompt_with_default(&format!("{label}ディレクトリ"), &default_display)?;
         let path = PathBuf::from(input);
         if path.as_os_str().is_empty() {
             println!("空のパスは指定できません。");
             continue;
         }
-        let confirm = prompt_yes_no(
-            &format!("デプロイ元は {} でよいですか？", path.display()),
-            true,
+        let confirm = prompt_confirm(
+            answers,
+            &format!("{label}は {} でよいですか？", path.display()),
         )?;
         if confirm {
             return Ok(path);
@@ -45,21 +40,31 @@ pub(crate) fn prompt_deploy_source_dir() -> io::Result<PathBuf> {
     }
 }
 
-pub(crate) fn confirm_existing_install(path: &Path) -> Result<bool, Box<dyn Error>> {
+/// 既存ファイルがある場合に上書きを確かめ、その回答を返す。確かめる必要が無ければ `None`。
+pub(crate) fn confirm_existing_install(
+    path: &Path,
+    answers: &Answers,
+) -> Result<Option<bool>, Box<dyn Error>> {
     if path.exists() {
         if !path.is_dir() {
             return Err("インストール先がディレクトリではありません。".into());
         }
         let mut entries = fs::read_dir(path)?;
         if entries.next().is_some() {
-            let confirm =
-                prompt_yes_no("既存ファイルが存在します。上書きして続行しますか？", false)?;
-            if !confirm {
-                return Ok(false);
-            }
+            let confirm = match answers.overwrite {
+                Some(value) => value,
+                None if answers.non_interactive => {
+                    return Err(
+                        "既存ファイルが存在します。上書きする場合は --overwrite を指定してください。"
+                            .into(),
+                    );
+                }
+                None => prompt_yes_no("既存ファイルが存在します。上書きして続行しますか？", false)?,
+            };
+            return Ok(Some(confirm));
         }
     }
-    Ok(true)
+    Ok(None)
 }
 
 fn default_install_dir() -> PathBuf {
@@ -1,15 +1,44 @@
 use std::io;
 
-use super::input::{prompt_with_default, prompt_yes_no};
+use super::answers::{Answers, missing_answer};
+use super::input::{prompt_confirm, prompt_with_default};
 
 const DEFAULT_XMS: &str = "256M";
 const DEFAULT_XMX: &str = "512M";
 
-pub(crate) fn prompt_memory() -> io::Result<(String, String)> {
+/// `recorded` は状態ファイルに記録された前回の値で、対話時の既定値にする。
+pub(crate) fn prompt_memory(
+    answers: &Answers,
+    recorded: Option<&(String, String)>,
+) -> io::Result<(String, String)> {
+    if let (Some(xms), Some(xmx)) = (&answers.xms, &answers.xmx) {
+        return Ok((xms.clone(), xmx.clone()));
+    }
+    if answers.non_interactive {
+        let flag = if answers.xms.is_none() {
+            "--xms"
+        } else {
+            "--xmx"
+        };
+        return Err(missing_answer(flag));
+    }
+    let (default_xms, default_xmx) = match recorded {
+        Some((xms, xmx)) => (xms.as_str(), xmx.as_str()),
+        None => (DEFAULT_XMS, DEFAULT_XMX),
+    };
     loop {
-        let xms = prompt_with_default("起動メモリ Xms", DEFAULT_XMS)?;
-        let xmx = prompt_with_default("最大メモリ Xmx", DEFAULT_XMX)?;
-        let confirm = prompt_yes_no(&format!("Xms={} / Xmx={} でよいですか？", xms, xmx), true)?;
+        let xms = match &answers.xms {
+            Some(value) => value.clone(),
+            None => prompt_with_default("起動メモリ Xms", default_xms)?,
+        };
+        let xmx = match &answers.xmx {
+            Some(value) => value.clone(),
+            None => prompt_with_default("最大メモリ Xmx", default_xmx)?,
+        };
+        let confirm = prompt_confirm(
+            answers,
+            &format!("Xms={} / Xmx={} でよいですか？", xms, xmx),
+        )?;
         if confirm {
             return Ok((xms, xmx));
         }
@@ -1,9 +1,15 @@
+mod answers;
 pub(crate) mod input;
 mod install_dir;
 mod memory;
+mod update_timer;
 mod version;
 
-pub(crate) use input::prompt_yes_no;
-pub(crate) use install_dir::{confirm_existing_install, prompt_deploy_source_dir, prompt_install_dir};
+pub(crate) use answers::Answers;
+pub(crate) use input::{prompt_confirm, prompt_yes_no_answer};
+pub(crate) use install_dir::{
+    confirm_existing_install, prompt_deploy_source_dir, prompt_install_dir,
+};
 pub(crate) use memory::prompt_memory;
+pub(crate) use update_timer::{UpdateTimer, prompt_update_timer};
 pub(crate) use version::prompt_version;
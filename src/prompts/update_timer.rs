@@ -0,0 +1,87 @@
+use std::io;
+
+use super::answers::Answers;
+use super::input::{prompt_with_default, prompt_yes_no};
+use crate::version::KNOWN_KINDS;
+
+const DEFAULT_SCHEDULE: &str = "weekly";
+const DEFAULT_KIND: &str = "stable";
+
+/// 自動更新のタイマー（`velocity-update.service` / `velocity-update.timer`）の設定。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UpdateTimer {
+    /// systemd の `OnCalendar` の値（`weekly` / `Mon *-*-* 04:00` など）。
+    pub(crate) schedule: String,
+    /// 追う type。この type の最新が新しい場合だけ更新する。
+    pub(crate) kind: String,
+}
+
+/// 生成しない場合は `None`。非対話モードでは `--update-schedule` がある場合だけ生成する。
+///
+/// 値はユニットファイルにそのまま書くため、type は既知のものだけ、スケジュールは制御文字を含まないものだけ受け付ける。
+pub(crate) fn prompt_update_timer(answers: &Answers) -> io::Result<Option<UpdateTimer>> {
+    if let Some(kind) = &answers.update_kind {
+        check_kind(kind).map_err(io::Error::other)?;
+    }
+    if let Some(schedule) = &answers.update_schedule {
+        check_schedule(schedule).map_err(io::Error::other)?;
+        return Ok(Some(UpdateTimer {
+            schedule: schedule.clone(),
+            kind: answers
+                .update_kind
+                .clone()
+                .unwrap_or_else(|| DEFAULT_KIND.to_string()),
+        }));
+    }
+    // タイマーは --update-schedule を指定した場合だけ生成する
+    if answers.non_interactive {
+        return Ok(None);
+    }
+    if !prompt_yes_no("自動更新の systemd タイマーを生成しますか？", false)? {
+        return Ok(None);
+    }
+    let schedule = prompt_valid(
+        "実行スケジュール（OnCalendar）",
+        DEFAULT_SCHEDULE,
+        check_schedule,
+    )?;
+    let kind = match &answers.update_kind {
+        Some(kind) => kind.clone(),
+        None => prompt_valid("更新する type", DEFAULT_KIND, check_kind)?,
+    };
+    Ok(Some(UpdateTimer { schedule, kind }))
+}
+
+fn prompt_valid(
+    message: &str,
+    default: &str,
+    check: fn(&str) -> Result<(), String>,
+) -> io::Result<String> {
+    loop {
+        let value = prompt_with_default(message, default)?;
+        match check(&value) {
+            Ok(()) => return Ok(value),
+            Err(err) => println!("{err}"),
+        }
+    }
+}
+
+fn check_kind(kind: &str) -> Result<(), String> {
+    if KNOWN_KINDS.contains(&kind) {
+        return Ok(());
+    }
+    Err(format!(
+        "自動更新の type は {} のいずれかを指定してください: {kind}",
+        KNOWN_KINDS.join(" / ")
+    ))
+}
+
+fn check_schedule(schedule: &str) -> Result<(), String> {
+    if schedule.trim().is_empty() {
+        return Err("自動更新のスケジュールが空です。".to_string());
+    }
+    if schedule.chars().any(char::is_control) {
+        return Err("自動更新のスケジュールに改行などの制御文字は使えません。".to_string());
+    }
+    Ok(())
+}
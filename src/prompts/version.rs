@@ -1,24 +1,249 @@
 use std::io;
 
-use crate::version::VersionInfo;
+use crate::version::{VersionInfo, resolve_version};
 
-use super::input::{prompt_usize_with_default, prompt_yes_no};
+use super::answers::{Answers, missing_answer};
+use super::input::{prompt_confirm, prompt_line};
 
-pub(crate) fn prompt_version(versions: &[VersionInfo]) -> io::Result<VersionInfo> {
+const PAGE_SIZE: usize = 20;
+
+/// `current` はインストール済みのバージョン。一覧にあれば既定の選択にする。
+pub(crate) fn prompt_version(
+    versions: &[VersionInfo],
+    answers: &Answers,
+    current: Option<&str>,
+) -> io::Result<VersionInfo> {
+    if let Some(requested) = &answers.version {
+        let chosen = resolve_version(versions, requested)
+            .cloned()
+            .ok_or_else(|| {
+                io::Error::other(format!("指定されたバージョンが見つかりません: {requested}"))
+            })?;
+        verify_recorded_version(&chosen, answers)?;
+        return Ok(chosen);
+    }
+    if answers.non_interactive {
+        return Err(missing_answer("--version"));
+    }
+    let default_version = default_version(versions, current);
+    let mut filter = VersionFilter::default();
     loop {
-        println!();
-        println!("利用可能なバージョン一覧:");
-        for (idx, version) in versions.iter().enumerate() {
-            println!("{:>3}. {}", idx + 1, version.display_label());
-        }
-        let selection = prompt_usize_with_default("番号で選択してください", 1, 1..=versions.len())?;
-        let chosen = versions[selection - 1].clone();
-        let confirm = prompt_yes_no(
+        let filtered = filter.apply(versions);
+        let page_count = filtered.len().div_ceil(PAGE_SIZE).max(1);
+        filter.page = filter.page.min(page_count - 1);
+        print_version_page(&filtered, &filter, page_count);
+
+        let input = prompt_line(&format!(
+            "番号またはバージョンを入力してください [{}]: ",
+            default_version.version
+        ))?;
+        let chosen = match parse_command(&input) {
+            VersionCommand::Default => default_version,
+            VersionCommand::Select(number) => match filtered.get(number.wrapping_sub(1)) {
+                Some(version) => version,
+                None => {
+                    println!("範囲内の番号を入力してください。");
+                    continue;
+                }
+            },
+            VersionCommand::NextPage => {
+                if filter.page + 1 < page_count {
+                    filter.page += 1;
+                } else {
+                    println!("最後のページです。");
+                }
+                continue;
+            }
+            VersionCommand::PrevPage => {
+                if filter.page > 0 {
+                    filter.page -= 1;
+                } else {
+                    println!("最初のページです。");
+                }
+                continue;
+            }
+            VersionCommand::Search(query) => {
+                filter.query = query;
+                filter.page = 0;
+                continue;
+            }
+            VersionCommand::Kind(kind) => {
+                filter.kind = kind;
+                filter.page = 0;
+                continue;
+            }
+            VersionCommand::Literal(spec) => match resolve_version(versions, &spec) {
+                Some(version) => version,
+                None => {
+                    println!("指定されたバージョンが見つかりません: {spec}");
+                    continue;
+                }
+            },
+        };
+        let confirm = prompt_confirm(
+            answers,
             &format!("{} を選択しますか？", chosen.display_label()),
-            true,
         )?;
         if confirm {
-            return Ok(chosen);
+            return Ok(chosen.clone());
+        }
+    }
+}
+
+/// 一覧の絞り込み状態。番号は絞り込み後の一覧に対して 1 から振る。
+#[derive(Debug, Default)]
+struct VersionFilter {
+    kind: Option<String>,
+    query: Option<String>,
+    page: usize,
+}
+
+impl VersionFilter {
+    fn apply<'a>(&self, versions: &'a [VersionInfo]) -> Vec<&'a VersionInfo> {
+        versions
+            .iter()
+            .filter(|version| self.kind.as_ref().is_none_or(|kind| &version.kind == kind))
+            .filter(|version| {
+                self.query
+                    .as_ref()
+                    .is_none_or(|query| version.version.contains(query.as_str()))
+            })
+            .collect()
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(kind) = &self.kind {
+            parts.push(format!("種類: {kind}"));
+        }
+        if let Some(query) = &self.query {
+            parts.push(format!("検索: {query}"));
+        }
+        if parts.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", parts.join(", "))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum VersionCommand {
+    Default,
+    Select(usize),
+    NextPage,
+    PrevPage,
+    Search(Option<String>),
+    Kind(Option<String>),
+    Literal(String),
+}
+
+fn parse_command(input: &str) -> VersionCommand {
+    let input = input.trim();
+    if input.is_empty() {
+        return VersionCommand::Default;
+    }
+    if let Ok(number) = input.parse::<usize>() {
+        return VersionCommand::Select(number);
+    }
+    if let Some(query) = input.strip_prefix('/') {
+        let query = query.trim();
+        return VersionCommand::Search((!query.is_empty()).then(|| query.to_string()));
+    }
+    if let Some(kind) = input.strip_prefix(':') {
+        let kind = kind.trim().to_ascii_lowercase();
+        return VersionCommand::Kind((!kind.is_empty() && kind != "all").then_some(kind));
+    }
+    match input {
+        "n" => VersionCommand::NextPage,
+        "p" => VersionCommand::PrevPage,
+        _ => VersionCommand::Literal(input.to_string()),
+    }
+}
+
+fn print_version_page(filtered: &[&VersionInfo], filter: &VersionFilter, page_count: usize) {
+    println!();
+    println!(
+        "利用可能なバージョン一覧{} ({}/{} ページ, {} 件):",
+        filter.describe(),
+        filter.page + 1,
+        page_count,
+        filtered.len()
+    );
+    if filtered.is_empty() {
+        println!("  該当するバージョンがありません。");
+    }
+    let start = filter.page * PAGE_SIZE;
+    for (idx, version) in filtered.iter().enumerate().skip(start).take(PAGE_SIZE) {
+        println!("{:>3}. {}", idx + 1, version.display_label());
+    }
+    println!(
+        "  番号 / バージョン (例: 3.4, latest) で選択、n / p でページ移動、/文字列 で検索、:stable / :beta / :snapshot / :all で種類を絞り込み"
+    );
+}
+
+/// 既定の選択。インストール済みのバージョン、無ければ stable の最新、それも無ければ先頭。
+fn default_version<'a>(versions: &'a [VersionInfo], current: Option<&str>) -> &'a VersionInfo {
+    current
+        .and_then(|current| versions.iter().find(|version| version.version == current))
+        .or_else(|| resolve_version(versions, "latest-stable"))
+        .unwrap_or(&versions[0])
+}
+
+fn verify_recorded_version(chosen: &VersionInfo, answers: &Answers) -> io::Result<()> {
+    let mut compared = false;
+    for (algorithm, expected) in answers.checksums.iter() {
+        let Some(actual) = chosen.checksums.get(algorithm) else {
+            continue;
+        };
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(io::Error::other(format!(
+                "{} の {algorithm} が指定された値と一致しません: expected={expected}, actual={actual}",
+                chosen.version
+            )));
         }
+        compared = true;
+    }
+    if !answers.checksums.is_empty() && !compared {
+        return Err(io::Error::other(format!(
+            "{} には指定されたチェックサムと同じ種類のチェックサムがありません。",
+            chosen.version
+        )));
+    }
+    if let Some(build) = answers.build
+        && chosen.build != Some(build)
+    {
+        return Err(io::Error::other(format!(
+            "{} の build が指定された値と一致しません: expected={}",
+            chosen.version, build
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_command_recognizes_navigation_and_filters() {
+        assert_eq!(parse_command(""), VersionCommand::Default);
+        assert_eq!(parse_command(" 3 "), VersionCommand::Select(3));
+        assert_eq!(parse_command("n"), VersionCommand::NextPage);
+        assert_eq!(parse_command("p"), VersionCommand::PrevPage);
+        assert_eq!(
+            parse_command("/3.4"),
+            VersionCommand::Search(Some("3.4".to_string()))
+        );
+        assert_eq!(parse_command("/"), VersionCommand::Search(None));
+        assert_eq!(
+            parse_command(":Beta"),
+            VersionCommand::Kind(Some("beta".to_string()))
+        );
+        assert_eq!(parse_command(":all"), VersionCommand::Kind(None));
+        assert_eq!(
+            parse_command("3.4"),
+            VersionCommand::Literal("3.4".to_string())
+        );
     }
 }
@@ -0,0 +1,122 @@
+use std::cmp::Reverse;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::installed::script_jars;
+use crate::manifest::{JarManifest, VELOCITY_MAIN_CLASS};
+
+/// インストール先の直下にある、起動スクリプトが参照していない Velocity の jar（新しい順）。
+///
+/// `MANIFEST.MF` の `Main-Class` が Velocity のものだけを対象にする。
+/// `Main-Class` を読めない jar は `velocity-*.jar` の名前で判断する。
+/// `current`（直前に配置した jar など）は参照されていなくても対象にしない。
+pub fn unreferenced_jars(
+    install_dir: &Path,
+    current: Option<&str>,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    // 同じ名前の jar は場所が違っても残す（安全側に倒す）
+    let kept: Vec<OsString> = script_jars(install_dir)?
+        .iter()
+        .map(String::as_str)
+        .chain(current)
+        .filter_map(|jar| Path::new(jar).file_name().map(|name| name.to_os_string()))
+        .collect();
+    let mut jars = Vec::new();
+    for entry in fs::read_dir(install_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if !entry.file_type()?.is_file()
+            || path.extension().is_none_or(|extension| extension != "jar")
+            || kept.contains(&entry.file_name())
+            || !is_velocity_jar(&path)
+        {
+            continue;
+        }
+        jars.push((entry.metadata()?.modified()?, path));
+    }
+    jars.sort_by_key(|(modified, _)| Reverse(*modified));
+    Ok(jars.into_iter().map(|(_, path)| path).collect())
+}
+
+/// 参照されていない jar のうち、新しい `keep` 個を残して削除する。削除した jar を返す。
+pub fn prune_old_jars(
+    install_dir: &Path,
+    current: &str,
+    keep: usize,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let stale: Vec<PathBuf> = unreferenced_jars(install_dir, Some(current))?
+        .into_iter()
+        .skip(keep)
+        .collect();
+    for path in &stale {
+        fs::remove_file(path)?;
+    }
+    Ok(stale)
+}
+
+fn is_velocity_jar(path: &Path) -> bool {
+    match JarManifest::read(path).map(|manifest| manifest.main_class) {
+        Ok(Some(main_class)) => main_class == VELOCITY_MAIN_CLASS,
+        _ => path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with("velocity-")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn prune_keeps_referenced_current_and_newest_jars() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let dir = temp_dir.path();
+        fs::write(
+            dir.join("start.sh"),
+            "exec java -jar \"velocity-proxy-1.0.0.jar\"\n",
+        )
+        .expect("write script");
+        let now = SystemTime::now();
+        for (index, name) in [
+            "velocity-proxy-1.0.0.jar",
+            "velocity-proxy-1.1.0.jar",
+            "velocity-proxy-1.2.0.jar",
+            "velocity-proxy-1.3.0.jar",
+            "velocity-proxy-1.4.0.jar",
+            "other-tool.jar",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let path = dir.join(name);
+            fs::write(&path, b"jar").expect("write jar");
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(now - Duration::from_secs(60 * index as u64)))
+                .expect("set mtime");
+        }
+
+        let removed = prune_old_jars(dir, "velocity-proxy-1.1.0.jar", 1).expect("prune");
+        let removed: Vec<_> = removed
+            .iter()
+            .map(|path| {
+                path.file_name()
+                    .expect("name")
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            removed,
+            vec!["velocity-proxy-1.3.0.jar", "velocity-proxy-1.4.0.jar"]
+        );
+        assert!(dir.join("velocity-proxy-1.0.0.jar").exists());
+        assert!(dir.join("velocity-proxy-1.1.0.jar").exists());
+        assert!(dir.join("velocity-proxy-1.2.0.jar").exists());
+        assert!(dir.join("other-tool.jar").exists());
+    }
+}
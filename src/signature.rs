@@ -0,0 +1,217 @@
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ed25519_dalek::{Signature, VerifyingKey};
+use reqwest::StatusCode;
+use reqwest::blocking::Client;
+
+use crate::config::SignatureConfig;
+use crate::location;
+
+/// 設定で固定した公開鍵（ed25519）による、分離署名の検証。
+///
+/// 署名は対象の URL（またはパス）に `.sig` を付けた場所に置いた、64 バイトの署名の Base64。
+/// 署名が無い対象は、必須にしていなければ警告して続行する。
+#[derive(Debug, Clone)]
+pub struct SignatureVerifier {
+    keys: Vec<VerifyingKey>,
+    required: bool,
+}
+
+impl SignatureVerifier {
+    /// 公開鍵が無く、署名も必須にしていない場合は `None`（検証しない）。
+    pub fn from_config(config: &SignatureConfig) -> Result<Option<Self>, Box<dyn Error>> {
+        let required = config.required.unwrap_or(false);
+        let keys = config
+            .keys
+            .iter()
+            .flatten()
+            .map(|key| parse_public_key(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            if required {
+                return Err(
+                    "署名を必須にする場合は公開鍵を設定してください（[signature] keys）。".into(),
+                );
+            }
+            return Ok(None);
+        }
+        Ok(Some(Self { keys, required }))
+    }
+
+    /// `location` の内容 `data` を、`<location>.sig` の署名で検証する。
+    ///
+    /// 検証できた署名を返す。署名が無く続行した場合は `None`。
+    pub fn check(
+        &self,
+        client: &Client,
+        location: &str,
+        data: &[u8],
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        self.check_fetched(
+            location,
+            data,
+            fetch_signature(client, &signature_location(location)),
+        )
+    }
+
+    /// 取得済みの署名（`fetch_signature` の結果）で `check` と同じ検証をする。
+    pub fn check_fetched(
+        &self,
+        location: &str,
+        data: &[u8],
+        fetched: Result<Option<String>, Box<dyn Error>>,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        match fetched {
+            Ok(Some(signature)) => {
+                self.verify(location, data, &signature)?;
+                Ok(Some(signature))
+            }
+            Ok(None) => self.unsigned(location, "署名がありません").map(|()| None),
+            Err(err) => self
+                .unsigned(location, &format!("署名を取得できません（{err}）"))
+                .map(|()| None),
+        }
+    }
+
+    /// `data` が固定したいずれかの公開鍵で署名されていることを確かめる。
+    pub fn verify(
+        &self,
+        location: &str,
+        data: &[u8],
+        signature: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let signature = parse_signature(signature)
+            .map_err(|err| format!("署名の形式が不正です: {location} ({err})"))?;
+        if self
+            .keys
+            .iter()
+            .any(|key| key.verify_strict(data, &signature).is_ok())
+        {
+            return Ok(());
+        }
+        Err(format!("署名を検証できません（設定した公開鍵と一致しません）: {location}").into())
+    }
+
+    /// 署名が無い対象の扱い。必須ならエラー、そうでなければ警告して続行する。
+    pub fn unsigned(&self, location: &str, reason: &str) -> Result<(), Box<dyn Error>> {
+        if self.required {
+            return Err(format!("{reason}: {location}（署名が必須に設定されています）").into());
+        }
+        eprintln!("警告: {reason}。署名を検証せずに続行します: {location}");
+        Ok(())
+    }
+}
+
+/// 署名を置く場所。
+pub fn signature_location(location: &str) -> String {
+    format!("{location}.sig")
+}
+
+/// 署名ファイルを読み込む。存在しない場合は `None`。
+pub fn fetch_signature(client: &Client, location: &str) -> Result<Option<String>, Box<dyn Error>> {
+    if let Some(path) = location::local_path(location) {
+        return match fs::read_to_string(&path) {
+            Ok(text) => Ok(Some(text)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        };
+    }
+    let response = client.get(location).send()?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(response.error_for_status()?.text()?))
+}
+
+fn parse_public_key(key: &str) -> Result<VerifyingKey, Box<dyn Error>> {
+    let bytes: [u8; 32] = decode(key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("公開鍵は 32 バイトの Base64 で指定してください: {key}"))?;
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|err| format!("公開鍵が不正です: {key} ({err})").into())
+}
+
+fn parse_signature(signature: &str) -> Result<Signature, Box<dyn Error>> {
+    let bytes: [u8; 64] = decode(signature)?
+        .try_into()
+        .map_err(|_| "64 バイトではありません")?;
+    Ok(Signature::from_bytes(&bytes))
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    STANDARD.decode(value.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn config_with_key(key: &SigningKey, required: bool) -> SignatureConfig {
+        SignatureConfig {
+            keys: Some(vec![STANDARD.encode(key.verifying_key().to_bytes())]),
+            required: Some(required),
+        }
+    }
+
+    #[test]
+    fn verifies_signature_from_pinned_key() {
+        let trusted = SigningKey::from_bytes(&[7; 32]);
+        let other = SigningKey::from_bytes(&[8; 32]);
+        let verifier = SignatureVerifier::from_config(&config_with_key(&trusted, false))
+            .expect("config")
+            .expect("verifier");
+
+        let data = b"velocity-index";
+        let signature = STANDARD.encode(trusted.sign(data).to_bytes());
+        verifier
+            .verify("velocity.json", data, &format!("{signature}\n"))
+            .expect("verify");
+
+        let forged = STANDARD.encode(other.sign(data).to_bytes());
+        let message = verifier
+            .verify("velocity.json", data, &forged)
+            .expect_err("forged")
+            .to_string();
+        assert!(message.contains("公開鍵と一致しません"));
+        assert!(
+            verifier
+                .verify("velocity.json", b"tampered", &signature)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn unsigned_is_rejected_only_when_required() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let lenient = SignatureVerifier::from_config(&config_with_key(&key, false))
+            .expect("config")
+            .expect("verifier");
+        lenient
+            .unsigned("velocity.jar", "署名がありません")
+            .expect("warn only");
+
+        let strict = SignatureVerifier::from_config(&config_with_key(&key, true))
+            .expect("config")
+            .expect("verifier");
+        assert!(strict.unsigned("velocity.jar", "署名がありません").is_err());
+
+        assert!(
+            SignatureVerifier::from_config(&SignatureConfig::default())
+                .expect("config")
+                .is_none()
+        );
+        assert!(
+            SignatureVerifier::from_config(&SignatureConfig {
+                keys: None,
+                required: Some(true),
+            })
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,511 @@
+use std::error::Error;
+use std::path::Path;
+
+/// 起動スクリプトの書式。引用符や行の継続、コメントの書き方が異なる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    /// `start.sh`（POSIX sh）。
+    Sh,
+    /// `start.bat`（cmd.exe）。
+    Bat,
+}
+
+impl Shell {
+    /// 拡張子が `.bat` / `.cmd` なら `Bat`、それ以外は `Sh`。
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension)
+                if extension.eq_ignore_ascii_case("bat")
+                    || extension.eq_ignore_ascii_case("cmd") =>
+            {
+                Self::Bat
+            }
+            _ => Self::Sh,
+        }
+    }
+
+    fn is_quote(self, c: char) -> bool {
+        match self {
+            Self::Sh => c == '"' || c == '\'',
+            Self::Bat => c == '"',
+        }
+    }
+
+    fn is_continuation(self, c: char) -> bool {
+        match self {
+            Self::Sh => c == '\\',
+            Self::Bat => c == '^',
+        }
+    }
+
+    fn is_path_separator(self, c: char) -> bool {
+        match self {
+            Self::Sh => c == '/',
+            Self::Bat => c == '/' || c == '\\',
+        }
+    }
+
+    /// 行頭（空白を除く）から始まる `line` がコメントか。
+    fn is_comment(self, line: &str) -> bool {
+        match self {
+            Self::Sh => line.starts_with('#'),
+            Self::Bat => {
+                let line = line.strip_prefix('@').unwrap_or(line);
+                let rem = line
+                    .get(..3)
+                    .is_some_and(|word| word.eq_ignore_ascii_case("rem"))
+                    && line[3..].chars().next().is_none_or(char::is_whitespace);
+                rem || line.starts_with("::")
+            }
+        }
+    }
+}
+
+/// 起動スクリプトの java を起動するコマンド。
+///
+/// JVM オプションとプログラム引数は、スクリプトに書かれたまま（引用符を含む）で持つ。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaCommand {
+    /// java の実行ファイル（`java` や `"$JAVA_HOME/bin/java"` など）。
+    pub java: String,
+    /// `-jar` より前の JVM オプション。
+    pub jvm_options: Vec<String>,
+    /// `-jar` の値（引用符を外したもの）。
+    pub jar: String,
+    /// jar に続くプログラム引数。
+    pub args: Vec<String>,
+}
+
+impl JavaCommand {
+    /// インストール時に生成するコマンド（`java -Xms… -Xmx… -jar …`）。
+    pub fn new(xms: &str, xmx: &str, jar: &str) -> Self {
+        Self {
+            java: "java".to_string(),
+            jvm_options: vec![format!("-Xms{xms}"), format!("-Xmx{xmx}")],
+            jar: jar.to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    /// JVM オプションの `-Xms` / `-Xmx`。どちらかが無い場合は `None`。
+    pub fn memory(&self) -> Option<(String, String)> {
+        let last = |prefix: &str| {
+            self.jvm_options
+                .iter()
+                .rev()
+                .find_map(|option| unquote(option).strip_prefix(prefix).map(str::to_string))
+                .filter(|value| !value.is_empty())
+        };
+        Some((last("-Xms")?, last("-Xmx")?))
+    }
+
+    /// `start.sh` のひな形。
+    pub fn render_sh(&self) -> String {
+        format!(
+            "#!/usr/bin/env sh\nset -e\nDIR=\"$(cd \"$(dirname \"$0\")\" && pwd)\"\ncd \"$DIR\"\nexec {}\n",
+            self.command_line(Shell::Sh)
+        )
+    }
+
+    /// `start.bat` のひな形。
+    pub fn render_bat(&self) -> String {
+        format!(
+            "@echo off\r\nset \"DIR=%~dp0\"\r\ncd /d \"%DIR%\"\r\n{}\r\n",
+            self.command_line(Shell::Bat)
+        )
+    }
+
+    fn command_line(&self, shell: Shell) -> String {
+        let jar = format!("\"{}\"", escape_jar(&self.jar, shell, Some('"')));
+        std::iter::once(self.java.as_str())
+            .chain(self.jvm_options.iter().map(String::as_str))
+            .chain(["-jar", jar.as_str()])
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// 起動スクリプトと、その中の java を起動する行。
+///
+/// 書き直す場合は java の行の jar のファイル名（と `-Xms` / `-Xmx`）の部分だけを置き換え、
+/// ほかの行や空白・引用符・ディレクトリの指定はそのまま残す。
+#[derive(Debug, Clone)]
+pub struct StartScript {
+    contents: String,
+    shell: Shell,
+    /// java の行のトークン（`exec` を除く）。
+    tokens: Vec<Token>,
+    /// `tokens` のうち `-jar` の位置。
+    jar_flag: usize,
+}
+
+/// スクリプト中の 1 つのトークンの位置（バイト）。
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    start: usize,
+    end: usize,
+}
+
+impl StartScript {
+    /// `-jar <jar>` を含む最初のコマンドを java の行とする。無ければ `None`。
+    ///
+    /// 引用符（sh は `"` と `'`、bat は `"` だけ）の中の空白はトークンを区切らない。引用符は改行で閉じる。
+    /// 行末の `\`（sh）/ `^`（bat）は行を続ける。
+    /// `#`（sh）/ `rem` / `::`（bat）で始まる行はコメントとして読み飛ばす。
+    pub fn parse(contents: &str, shell: Shell) -> Option<Self> {
+        for command in split_commands(contents, shell) {
+            let raw = |token: &Token| &contents[token.start..token.end];
+            let Some(first) = command.first() else {
+                continue;
+            };
+            let tokens: Vec<Token> = if raw(first) == "exec" {
+                command[1..].to_vec()
+            } else {
+                command
+            };
+            let Some(jar_flag) = tokens.iter().position(|token| raw(token) == "-jar") else {
+                continue;
+            };
+            if jar_flag == 0 || jar_flag + 1 >= tokens.len() {
+                continue;
+            }
+            return Some(Self {
+                contents: contents.to_string(),
+                shell,
+                tokens,
+                jar_flag,
+            });
+        }
+        None
+    }
+
+    pub fn command(&self) -> JavaCommand {
+        let raw = |token: &Token| self.contents[token.start..token.end].to_string();
+        let jar: String = raw(&self.tokens[self.jar_flag + 1])
+            .chars()
+            .filter(|c| !self.shell.is_quote(*c))
+            .collect();
+        JavaCommand {
+            java: raw(&self.tokens[0]),
+            jvm_options: self.tokens[1..self.jar_flag].iter().map(raw).collect(),
+            jar,
+            args: self.tokens[self.jar_flag + 2..].iter().map(raw).collect(),
+        }
+    }
+
+    /// jar のファイル名と、指定があれば `-Xms` / `-Xmx` だけを置き換えたスクリプト。
+    ///
+    /// jar のファイル名の部分に変数（`$…` / `%…%`）などが含まれ、置き換えると意味が変わる場合はエラー。
+    pub fn render(
+        &self,
+        jar: &str,
+        memory: Option<(&str, &str)>,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut edits: Vec<(usize, usize, String)> = Vec::new();
+        let jar_token = self.tokens[self.jar_flag + 1];
+        let raw = &self.contents[jar_token.start..jar_token.end];
+        let name_start = raw
+            .rfind(|c| self.shell.is_path_separator(c))
+            .map_or(0, |index| index + 1);
+        let segment = &raw[name_start..];
+        let trimmed = segment.trim_start_matches(|c| self.shell.is_quote(c));
+        let name_begin = name_start + segment.len() - trimmed.len();
+        let name = trimmed.trim_end_matches(|c| self.shell.is_quote(c));
+        if name.is_empty()
+            || name.contains(|c: char| {
+                self.shell.is_quote(c) || matches!(c, '$' | '%' | '`' | '!' | '*' | '?')
+            })
+        {
+            return Err(format!("-jar の値のファイル名を書き換えられません: {raw}").into());
+        }
+        let quote = quote_state(&raw[..name_begin], self.shell);
+        edits.push((
+            jar_token.start + name_begin,
+            jar_token.start + name_begin + name.len(),
+            escape_jar(jar, self.shell, quote),
+        ));
+        if let Some((xms, xmx)) = memory {
+            // 無いものは `-jar` の直前にまとめて加える
+            let mut missing = String::new();
+            for (prefix, value) in [("-Xms", xms), ("-Xmx", xmx)] {
+                let option = format!("{prefix}{value}");
+                let existing = self.tokens[1..self.jar_flag].iter().find(|token| {
+                    unquote(&self.contents[token.start..token.end]).starts_with(prefix)
+                });
+                match existing {
+                    Some(token) => edits.push((token.start, token.end, option)),
+                    None => missing.push_str(&format!("{option} ")),
+                }
+            }
+            if !missing.is_empty() {
+                let at = self.tokens[self.jar_flag].start;
+                edits.push((at, at, missing));
+            }
+        }
+        // 後ろから置き換えて、前のトークンの位置をずらさない
+        edits.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+        let mut contents = self.contents.clone();
+        for (start, end, replacement) in edits {
+            contents.replace_range(start..end, &replacement);
+        }
+        Ok(contents)
+    }
+}
+
+/// スクリプトをコマンド（トークンの並び）に分ける。
+fn split_commands(contents: &str, shell: Shell) -> Vec<Vec<Token>> {
+    let mut commands = Vec::new();
+    let mut command: Vec<Token> = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = contents.char_indices();
+    while let Some((index, c)) = chars.next() {
+        if let Some(open) = quote {
+            if c == open {
+                quote = None;
+            }
+            // 閉じられていない引用符は行をまたがない
+            if c != '\n' {
+                continue;
+            }
+            quote = None;
+        }
+        if command.is_empty()
+            && start.is_none()
+            && !c.is_whitespace()
+            && shell.is_comment(&contents[index..])
+        {
+            // コメントは引用符などを解釈せずに行末まで読み飛ばす
+            for (_, next) in chars.by_ref() {
+                if next == '\n' {
+                    break;
+                }
+            }
+            continue;
+        }
+        match c {
+            c if shell.is_quote(c) => {
+                quote = Some(c);
+                start.get_or_insert(index);
+            }
+            '\n' => {
+                if let Some(begin) = start.take() {
+                    command.push(Token {
+                        start: begin,
+                        end: index,
+                    });
+                }
+                commands.push(std::mem::take(&mut command));
+            }
+            c if c.is_whitespace() => {
+                if let Some(begin) = start.take() {
+                    command.push(Token {
+                        start: begin,
+                        end: index,
+                    });
+                }
+            }
+            c if shell.is_continuation(c)
+                && start.is_none()
+                && continues_line(&contents[index + 1..]) =>
+            {
+                // 行の継続。改行まで読み飛ばす
+                for (_, next) in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            _ => {
+                start.get_or_insert(index);
+            }
+        }
+    }
+    if let Some(begin) = start {
+        command.push(Token {
+            start: begin,
+            end: contents.len(),
+        });
+    }
+    commands.push(command);
+    commands
+}
+
+fn continues_line(rest: &str) -> bool {
+    let rest = rest.strip_prefix('\r').unwrap_or(rest);
+    rest.starts_with('\n')
+}
+
+/// `prefix` の末尾で開いている引用符。
+fn quote_state(prefix: &str, shell: Shell) -> Option<char> {
+    prefix.chars().fold(None, |open, c| match open {
+        Some(quote) if c == quote => None,
+        Some(quote) => Some(quote),
+        None if shell.is_quote(c) => Some(c),
+        None => None,
+    })
+}
+
+/// jar 名を、`quote` の中（`None` は引用符の外）に書ける形にする。
+fn escape_jar(jar: &str, shell: Shell, quote: Option<char>) -> String {
+    let plain = jar
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '+'));
+    let escaped = match (shell, quote) {
+        (_, None) if plain => return jar.to_string(),
+        (Shell::Sh, Some('\'')) => return jar.replace('\'', r"'\''"),
+        (Shell::Sh, _) => jar
+            .chars()
+            .flat_map(|c| match c {
+                '\\' | '$' | '`' | '"' => vec!['\\', c],
+                c => vec![c],
+            })
+            .collect(),
+        // java のランチャーは `\"` を引用符そのものとして受け取る
+        (Shell::Bat, _) => jar.replace('%', "%%").replace('"', "\\\""),
+    };
+    match quote {
+        Some(_) => escaped,
+        None => format!("\"{escaped}\""),
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim_matches(|c| c == '"' || c == '\'')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_java_options_jar_and_arguments() {
+        let script = StartScript::parse(
+            "#!/usr/bin/env sh\n# exec java -jar old.jar\n# Don't edit the jar name\nexport TZ=UTC\nexec \"$JAVA_HOME/bin/java\" -Xms1G -Xmx2G \\\n  -XX:+UseG1GC -Dlog4j.configurationFile=\"log 4j.xml\" -jar \"velocity-1.0.0.jar\" --port 25577 \"$@\"\n",
+            Shell::Sh,
+        )
+        .expect("parse");
+        let command = script.command();
+        assert_eq!(command.java, "\"$JAVA_HOME/bin/java\"");
+        assert_eq!(
+            command.jvm_options,
+            vec![
+                "-Xms1G",
+                "-Xmx2G",
+                "-XX:+UseG1GC",
+                "-Dlog4j.configurationFile=\"log 4j.xml\""
+            ]
+        );
+        assert_eq!(command.jar, "velocity-1.0.0.jar");
+        assert_eq!(command.args, vec!["--port", "25577", "\"$@\""]);
+        assert_eq!(command.memory(), Some(("1G".to_string(), "2G".to_string())));
+
+        let bat = StartScript::parse(
+            "@echo off\r\nrem don't java -jar old.jar\r\n:: it's fine\r\njava -Xms1G -Xmx1G -Dmotd=don't -jar \"velocity-1.0.0.jar\" %*\r\n",
+            Shell::Bat,
+        )
+        .expect("parse bat");
+        let command = bat.command();
+        assert_eq!(command.jvm_options[2], "-Dmotd=don't");
+        assert_eq!(command.jar, "velocity-1.0.0.jar");
+        assert_eq!(command.args, vec!["%*"]);
+
+        // 閉じられていない引用符は次の行に持ち越さない
+        let unclosed = StartScript::parse(
+            "echo don't\nexec java -jar \"velocity-1.0.0.jar\"\n",
+            Shell::Sh,
+        )
+        .expect("parse unclosed");
+        assert_eq!(unclosed.command().jar, "velocity-1.0.0.jar");
+        assert!(StartScript::parse("java -version\n", Shell::Sh).is_none());
+    }
+
+    #[test]
+    fn render_changes_only_jar_and_memory() {
+        let contents = "#!/usr/bin/env sh\nexec java -Xms1G -Xmx2G  -XX:+UseG1GC -jar \"velocity-1.0.0.jar\" --port 25577\n";
+        let script = StartScript::parse(contents, Shell::Sh).expect("parse");
+        assert_eq!(
+            script.render("velocity-1.1.0.jar", None).expect("render"),
+            "#!/usr/bin/env sh\nexec java -Xms1G -Xmx2G  -XX:+UseG1GC -jar \"velocity-1.1.0.jar\" --port 25577\n"
+        );
+        assert_eq!(
+            script
+                .render("velocity-1.1.0.jar", Some(("2G", "4G")))
+                .expect("render"),
+            "#!/usr/bin/env sh\nexec java -Xms2G -Xmx4G  -XX:+UseG1GC -jar \"velocity-1.1.0.jar\" --port 25577\n"
+        );
+
+        let bare = StartScript::parse("java\t-jar  old.jar\r\n", Shell::Bat).expect("parse");
+        assert_eq!(
+            bare.render("new.jar", Some(("1G", "1G"))).expect("render"),
+            "java\t-Xms1G -Xmx1G -jar  new.jar\r\n"
+        );
+    }
+
+    #[test]
+    fn render_keeps_directory_of_jar() {
+        let render = |contents: &str, shell: Shell| {
+            StartScript::parse(contents, shell)
+                .expect("parse")
+                .render("velocity-2.jar", None)
+        };
+        assert_eq!(
+            render("exec java -jar \"$DIR\"/velocity-1.0.0.jar\n", Shell::Sh).expect("render"),
+            "exec java -jar \"$DIR\"/velocity-2.jar\n"
+        );
+        assert_eq!(
+            render("exec java -jar \"$DIR/velocity-1.0.0.jar\"\n", Shell::Sh).expect("render"),
+            "exec java -jar \"$DIR/velocity-2.jar\"\n"
+        );
+        assert_eq!(
+            render("java -jar \"%DIR%\\velocity-1.0.0.jar\"\r\n", Shell::Bat).expect("render"),
+            "java -jar \"%DIR%\\velocity-2.jar\"\r\n"
+        );
+        // ファイル名そのものが変数の場合は書き換えない
+        assert!(render("exec java -jar \"$JAR\"\n", Shell::Sh).is_err());
+        assert!(render("java -jar \"%DIR%velocity.jar\"\r\n", Shell::Bat).is_err());
+    }
+
+    #[test]
+    fn render_escapes_special_characters_in_jar() {
+        let render = |contents: &str, shell: Shell, jar: &str| {
+            StartScript::parse(contents, shell)
+                .expect("parse")
+                .render(jar, None)
+                .expect("render")
+        };
+        assert_eq!(
+            render("exec java -jar \"old.jar\"\n", Shell::Sh, "a$b\"c.jar"),
+            "exec java -jar \"a\\$b\\\"c.jar\"\n"
+        );
+        assert_eq!(
+            render("exec java -jar old.jar\n", Shell::Sh, "my $jar.jar"),
+            "exec java -jar \"my \\$jar.jar\"\n"
+        );
+        assert_eq!(
+            render("exec java -jar 'old.jar'\n", Shell::Sh, "it's.jar"),
+            "exec java -jar 'it'\\''s.jar'\n"
+        );
+        assert_eq!(
+            render("java -jar old.jar\r\n", Shell::Bat, "100%.jar"),
+            "java -jar \"100%%.jar\"\r\n"
+        );
+    }
+
+    #[test]
+    fn generated_scripts_round_trip() {
+        let command = JavaCommand::new("256M", "512M", "velocity.jar");
+        for (rendered, shell) in [
+            (command.render_sh(), Shell::Sh),
+            (command.render_bat(), Shell::Bat),
+        ] {
+            assert_eq!(
+                StartScript::parse(&rendered, shell)
+                    .expect("parse")
+                    .command(),
+                command
+            );
+        }
+    }
+}
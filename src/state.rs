@@ -0,0 +1,200 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::unix_now;
+use crate::checksum::Algorithm;
+use crate::installed::{detect_existing_memory, script_jar};
+
+/// インストール先に置く状態ファイルの名前。
+pub const STATE_FILE_NAME: &str = ".mc-velocity-installer.json";
+
+/// インストール・jar 再取得・デプロイのたびに記録する、インストール先の状態。
+///
+/// 次回以降の実行で回答の既定値にし、記録後に手で変更された箇所（ずれ）を検出する。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InstallState {
+    /// 記録したインストーラのバージョン。
+    pub installer_version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<u64>,
+    /// 起動スクリプトが起動する jar。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jar: Option<String>,
+    /// 配置した jar の sha256。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// バージョン一覧の取得元。手元の jar（`--jar`）の場合は無し。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xms: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xmx: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<ServiceState>,
+    /// デプロイで作った場合のデプロイ元。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deployed_from: Option<PathBuf>,
+    /// 最初に記録した日時（UNIX 秒）。
+    pub installed_at: u64,
+    /// 最後に記録した日時（UNIX 秒）。
+    pub updated_at: u64,
+}
+
+/// `velocity.service` に書いた内容。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceState {
+    pub working_directory: PathBuf,
+    pub exec_start: PathBuf,
+    pub user: String,
+    pub group: String,
+}
+
+impl InstallState {
+    pub fn path(install_dir: &Path) -> PathBuf {
+        install_dir.join(STATE_FILE_NAME)
+    }
+
+    /// 状態ファイルが無い場合は `None`。
+    pub fn load(install_dir: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        let path = Self::path(install_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path)?;
+        let state = serde_json::from_str(&contents)
+            .map_err(|err| format!("状態ファイルの形式が不正です: {} ({err})", path.display()))?;
+        Ok(Some(state))
+    }
+
+    /// 記録日時を更新して保存する。最初の記録日時は引き継ぐ。
+    pub fn save(&mut self, install_dir: &Path) -> Result<(), Box<dyn Error>> {
+        let now = unix_now();
+        if self.installed_at == 0 {
+            self.installed_at = now;
+        }
+        self.updated_at = now;
+        let mut contents = serde_json::to_string_pretty(self)?;
+        contents.push('\n');
+        fs::write(Self::path(install_dir), contents)?;
+        Ok(())
+    }
+
+    /// 記録した起動メモリ。
+    pub fn memory(&self) -> Option<(String, String)> {
+        match (&self.xms, &self.xmx) {
+            (Some(xms), Some(xmx)) => Some((xms.clone(), xmx.clone())),
+            _ => None,
+        }
+    }
+
+    /// 記録と現在のインストール先の食い違い（起動スクリプト・jar・velocity.service）。
+    pub fn drift(&self, install_dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut drift = Vec::new();
+        if let Some(recorded) = &self.jar {
+            match script_jar(install_dir)? {
+                Some((script, jar)) if &jar != recorded => drift.push(format!(
+                    "{script} が起動する jar が記録と異なります: 記録={recorded}, 現在={jar}"
+                )),
+                Some(_) => {}
+                None => drift.push("起動スクリプトがありません".to_string()),
+            }
+            let jar_path = install_dir.join(recorded);
+            if !jar_path.exists() {
+                drift.push(format!("記録した jar がありません: {recorded}"));
+            } else if let Some(sha256) = &self.sha256
+                && Algorithm::Sha256.digest_file(&jar_path)? != *sha256
+            {
+                drift.push(format!("{recorded} の内容が記録と異なります（sha256）"));
+            }
+        }
+        if let Some((xms, xmx)) = self.memory()
+            && let Some((current_xms, current_xmx)) = detect_existing_memory(install_dir)?
+            && (current_xms != xms || current_xmx != xmx)
+        {
+            drift.push(format!(
+                "起動メモリが記録と異なります: 記録=Xms {xms} / Xmx {xmx}, 現在=Xms {current_xms} / Xmx {current_xmx}"
+            ));
+        }
+        if let Some(service) = &self.service {
+            let path = install_dir.join("velocity.service");
+            match fs::read_to_string(&path) {
+                Ok(contents) => {
+                    if read_service(&contents) != *service {
+                        drift.push("velocity.service が記録と異なります".to_string());
+                    }
+                }
+                Err(_) => drift.push("velocity.service がありません".to_string()),
+            }
+        }
+        Ok(drift)
+    }
+}
+
+/// `velocity.service` から記録する項目を読み取る。
+pub fn read_service(contents: &str) -> ServiceState {
+    let mut service = ServiceState::default();
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key.trim() {
+            "WorkingDirectory" => service.working_directory = PathBuf::from(value.trim()),
+            "ExecStart" => service.exec_start = PathBuf::from(value.trim()),
+            "User" => service.user = value.trim().to_string(),
+            "Group" => service.group = value.trim().to_string(),
+            _ => {}
+        }
+    }
+    service
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drift_reports_changes_after_recording() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let dir = temp_dir.path();
+        fs::write(
+            dir.join("start.sh"),
+            "exec java -Xms1G -Xmx2G -jar \"velocity.jar\"\n",
+        )
+        .expect("write script");
+        fs::write(dir.join("velocity.jar"), b"velocity-jar").expect("write jar");
+        let mut state = InstallState {
+            jar: Some("velocity.jar".to_string()),
+            sha256: Some(
+                Algorithm::Sha256
+                    .digest_file(&dir.join("velocity.jar"))
+                    .expect("digest"),
+            ),
+            xms: Some("1G".to_string()),
+            xmx: Some("2G".to_string()),
+            ..InstallState::default()
+        };
+        state.save(dir).expect("save");
+        let loaded = InstallState::load(dir).expect("load").expect("state");
+        assert!(loaded.installed_at > 0);
+        assert!(loaded.drift(dir).expect("drift").is_empty());
+
+        fs::write(
+            dir.join("start.sh"),
+            "exec java -Xms1G -Xmx4G -jar \"other.jar\"\n",
+        )
+        .expect("edit script");
+        fs::write(dir.join("velocity.jar"), b"patched").expect("edit jar");
+        let drift = loaded.drift(dir).expect("drift");
+        assert_eq!(drift.len(), 3, "{drift:?}");
+        assert!(drift[0].contains("記録=velocity.jar, 現在=other.jar"));
+        assert!(drift[1].contains("内容が記録と異なります（sha256）"));
+        assert!(drift[2].contains("現在=Xms 1G / Xmx 4G"));
+    }
+}
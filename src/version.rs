@@ -1,15 +1,33 @@
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::error::Error;
+use std::fmt;
+use std::fs;
 
+use reqwest::StatusCode;
+use reqwest::Url;
 use reqwest::blocking::Client;
-use serde::Deserialize;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::cache::{CachedResponse, IndexCache, format_timestamp, unix_now};
+use crate::checksum::Checksums;
+use crate::location;
+use crate::signature::{self, SignatureVerifier};
 
 pub const VERSION_INDEX_URL: &str = "https://minedeck.github.io/jars/velocity.json";
+pub const PAPERMC_API_URL: &str = "https://api.papermc.io/v2/projects/velocity";
+
+/// 一覧の `type` として受け付ける値。
+pub const KNOWN_KINDS: [&str; 3] = ["stable", "beta", "snapshot"];
 
 #[derive(Debug, Deserialize)]
 struct VelocityIndex {
     status: Option<String>,
-    data: BTreeMap<String, VelocityEntry>,
+    /// 重複したキーも検出できるよう、記載順のまま受け取ってからエントリごとに検証する。
+    #[serde(deserialize_with = "entries_in_order")]
+    data: Vec<(String, serde_json::Value)>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,19 +41,138 @@ struct VelocityEntry {
 
 #[derive(Debug, Deserialize)]
 struct Checksum {
+    #[serde(default)]
+    sha512: Option<String>,
+    #[serde(default)]
     sha256: Option<String>,
+    #[serde(default)]
+    sha1: Option<String>,
+}
+
+fn entries_in_order<'de, D>(deserializer: D) -> Result<Vec<(String, serde_json::Value)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct EntriesVisitor;
+
+    impl<'de> Visitor<'de> for EntriesVisitor {
+        type Value = Vec<(String, serde_json::Value)>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("バージョンをキーとするオブジェクト")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut entries = Vec::new();
+            while let Some(entry) = map.next_entry()? {
+                entries.push(entry);
+            }
+            Ok(entries)
+        }
+    }
+
+    deserializer.deserialize_map(EntriesVisitor)
+}
+
+#[derive(Debug, Deserialize)]
+struct PaperProject {
+    versions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PaperBuilds {
+    builds: Vec<PaperBuild>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PaperBuild {
+    build: u64,
+    channel: String,
+    downloads: BTreeMap<String, PaperDownload>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PaperDownload {
+    name: String,
+    sha256: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct VersionInfo {
     pub version: String,
     pub kind: String,
     pub url: String,
-    pub sha256: String,
+    #[serde(flatten)]
+    pub checksums: Checksums,
     pub build: Option<u64>,
 }
 
+/// Velocity のバージョン文字列（`X.Y.Z[-SNAPSHOT]`）を比較用に分解したもの。
+///
+/// 数値部分は数値として比較し、同じ数値ならプレリリース（`-SNAPSHOT` など）を古いとみなす。
+/// 数値として解釈できない文字列は最も古いものとして扱う。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VelocityVersion {
+    numbers: Vec<u64>,
+    pre_release: Option<String>,
+    raw: String,
+}
+
+impl VelocityVersion {
+    pub fn parse(raw: &str) -> Self {
+        let (base, pre_release) = match raw.split_once('-') {
+            Some((base, pre)) => (base, Some(pre.to_string())),
+            None => (raw, None),
+        };
+        let numbers = base
+            .split('.')
+            .map(|part| part.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_default();
+        Self {
+            numbers,
+            pre_release,
+            raw: raw.to_string(),
+        }
+    }
+
+    /// `3.4` のような数値の接頭辞に一致するか判定する。
+    fn starts_with(&self, prefix: &[u64]) -> bool {
+        !prefix.is_empty() && self.numbers.starts_with(prefix)
+    }
+}
+
+impl Ord for VelocityVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.numbers
+            .cmp(&other.numbers)
+            .then_with(|| match (&self.pre_release, &other.pre_release) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a.cmp(b),
+            })
+            .then_with(|| self.raw.cmp(&other.raw))
+    }
+}
+
+impl PartialOrd for VelocityVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl VersionInfo {
+    /// バージョン番号、次に build 番号で比較する。
+    pub fn cmp_release(&self, other: &Self) -> Ordering {
+        VelocityVersion::parse(&self.version)
+            .cmp(&VelocityVersion::parse(&other.version))
+            .then_with(|| self.build.cmp(&other.build))
+    }
+
     pub fn display_label(&self) -> String {
         let kind = colorize_kind(&self.kind);
         match self.build {
@@ -56,38 +193,614 @@ fn colorize_kind(kind: &str) -> String {
     }
 }
 
-pub fn fetch_versions(client: &Client, url: &str) -> Result<Vec<VersionInfo>, Box<dyn Error>> {
-    let text = client.get(url).send()?.error_for_status()?.text()?;
-    let index: VelocityIndex = serde_json::from_str(&text)?;
-    if let Some(status) = index.status.as_deref()
-        && status != "ok"
-    {
-        return Err(format!("バージョン一覧の取得に失敗しました: status={status}").into());
+/// バージョン一覧の取得元。
+pub trait VersionSource {
+    /// 表示用の取得元 URL。
+    fn location(&self) -> &str;
+
+    /// 並び替え前のバージョン一覧を取得する。
+    fn fetch(&self, client: &Client) -> Result<Vec<VersionInfo>, Box<dyn Error>>;
+
+    /// 通信せずにキャッシュ済みの一覧だけを使う取得元。キャッシュを使わない場合は `None`。
+    fn offline(&self) -> Option<Box<dyn VersionSource>> {
+        None
+    }
+}
+
+/// 取得元の種類。`--source` / `MC_VELOCITY_INDEX_SOURCE` で選ぶか、URL から判定する。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    /// minedeck の `velocity.json` 形式。
+    VelocityIndex,
+    /// PaperMC downloads API（v2）。
+    PaperMc,
+}
+
+impl SourceKind {
+    pub fn parse(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name {
+            "velocity-index" => Ok(Self::VelocityIndex),
+            "papermc" => Ok(Self::PaperMc),
+            _ => Err(format!(
+                "不明なバージョン一覧の取得元です: {name}（velocity-index / papermc）"
+            )
+            .into()),
+        }
+    }
+
+    /// `/v2/projects/<project>` を含む URL は PaperMC API とみなす。
+    pub fn detect(url: &str) -> Self {
+        if url.contains("/v2/projects/") {
+            Self::PaperMc
+        } else {
+            Self::VelocityIndex
+        }
+    }
+
+    pub fn default_url(self) -> &'static str {
+        match self {
+            Self::VelocityIndex => VERSION_INDEX_URL,
+            Self::PaperMc => PAPERMC_API_URL,
+        }
+    }
+
+    pub fn source(self, url: &str, options: &SourceOptions) -> Box<dyn VersionSource> {
+        match self {
+            Self::VelocityIndex => Box::new(
+                VelocityIndexSource::new(url)
+                    .with_cache(options.cache.clone())
+                    .with_skip_invalid_entries(options.skip_invalid_entries)
+                    .with_signature(options.signature.clone()),
+            ),
+            Self::PaperMc => Box::new(
+                PaperMcSource::new(url)
+                    .with_cache(options.cache.clone())
+                    .with_skip_invalid_entries(options.skip_invalid_entries)
+                    .with_signature(options.signature.clone()),
+            ),
+        }
+    }
+}
+
+/// 取得元に共通の設定。
+#[derive(Debug, Clone, Default)]
+pub struct SourceOptions {
+    pub cache: Option<IndexCache>,
+    /// 不正なエントリがあっても一覧全体をエラーにせず、そのエントリだけ除外する。
+    pub skip_invalid_entries: bool,
+    /// 一覧の署名を検証する。
+    pub signature: Option<SignatureVerifier>,
+}
+
+/// 優先順に並べた複数の取得元（ミラー）。取得に失敗したら次の取得元を試す。
+pub struct MirroredSource {
+    sources: Vec<Box<dyn VersionSource>>,
+}
+
+impl MirroredSource {
+    pub fn new(sources: Vec<Box<dyn VersionSource>>) -> Self {
+        Self { sources }
+    }
+}
+
+impl VersionSource for MirroredSource {
+    fn location(&self) -> &str {
+        self.sources
+            .first()
+            .map(|source| source.location())
+            .unwrap_or_default()
+    }
+
+    fn fetch(&self, client: &Client) -> Result<Vec<VersionInfo>, Box<dyn Error>> {
+        let mut failures = Vec::new();
+        for source in &self.sources {
+            // 空の一覧も失敗として扱い、次の取得元を試す。
+            match fetch_sorted(client, source.as_ref()) {
+                Ok(versions) => {
+                    eprintln!("バージョン一覧の取得元: {}", source.location());
+                    return Ok(versions);
+                }
+                Err(err) => {
+                    eprintln!(
+                        "警告: バージョン一覧を取得できませんでした: {} ({err})",
+                        source.location()
+                    );
+                    failures.push(format!("{} ({err})", source.location()));
+                }
+            }
+        }
+        Err(format!(
+            "すべての取得元でバージョン一覧の取得に失敗しました: {}",
+            failures.join(", ")
+        )
+        .into())
+    }
+
+    fn offline(&self) -> Option<Box<dyn VersionSource>> {
+        let sources: Vec<_> = self
+            .sources
+            .iter()
+            .filter_map(|source| source.offline())
+            .collect();
+        if sources.is_empty() {
+            return None;
+        }
+        Some(Box::new(MirroredSource::new(sources)))
+    }
+}
+
+/// minedeck の `velocity.json` 形式の一覧。
+///
+/// 場所は HTTP(S) / `file://` URL またはローカルのパス。
+/// 各エントリの `url` が相対パスの場合は一覧の場所を基準に解決する。
+pub struct VelocityIndexSource {
+    url: String,
+    cache: Option<IndexCache>,
+    skip_invalid_entries: bool,
+    signature: Option<SignatureVerifier>,
+}
+
+impl VelocityIndexSource {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            cache: None,
+            skip_invalid_entries: false,
+            signature: None,
+        }
+    }
+
+    pub fn with_cache(mut self, cache: Option<IndexCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_skip_invalid_entries(mut self, skip: bool) -> Self {
+        self.skip_invalid_entries = skip;
+        self
+    }
+
+    /// 一覧の内容を `<url>.sig` の署名で検証する。
+    pub fn with_signature(mut self, signature: Option<SignatureVerifier>) -> Self {
+        self.signature = signature;
+        self
+    }
+}
+
+impl VersionSource for VelocityIndexSource {
+    fn location(&self) -> &str {
+        &self.url
     }
 
-    let mut versions = Vec::new();
-    for (version, entry) in index.data {
-        let sha256 = entry
-            .checksum
-            .sha256
-            .ok_or_else(|| format!("sha256 が見つかりません: {version}"))?;
-        versions.push(VersionInfo {
-            version,
+    fn fetch(&self, client: &Client) -> Result<Vec<VersionInfo>, Box<dyn Error>> {
+        let base = location::location_url(&self.url)?;
+        let text = match location::local_path(&self.url) {
+            Some(path) => fs::read_to_string(&path).map_err(|err| {
+                format!("バージョン一覧を読み込めません: {} ({err})", path.display())
+            })?,
+            None => get_text(client, self.cache.as_ref(), &self.url)?,
+        };
+        if let Some(signature) = &self.signature {
+            match (&self.cache, location::local_path(&self.url)) {
+                (Some(cache), None) => signature.check_fetched(
+                    &self.url,
+                    text.as_bytes(),
+                    get_signature(client, cache, &self.url),
+                ),
+                _ => signature.check(client, &self.url, text.as_bytes()),
+            }?;
+        }
+        let index: VelocityIndex = serde_json::from_str(&text)?;
+        if let Some(status) = index.status.as_deref()
+            && status != "ok"
+        {
+            return Err(format!("バージョン一覧の取得に失敗しました: status={status}").into());
+        }
+
+        let mut versions = Vec::new();
+        let mut problems = Vec::new();
+        let mut seen = BTreeMap::new();
+        for (version, raw) in index.data {
+            let entry_problems = match parse_entry(&base, &version, raw) {
+                Ok(info) => match seen.insert(version.clone(), info.build) {
+                    Some(build) => vec![format!(
+                        "同じバージョンのエントリが重複しています（build {}）",
+                        build.map_or_else(|| "なし".to_string(), |build| build.to_string())
+                    )],
+                    None => {
+                        versions.push(info);
+                        continue;
+                    }
+                },
+                Err(entry_problems) => entry_problems,
+            };
+            problems.extend(
+                entry_problems
+                    .into_iter()
+                    .map(|problem| format!("{version}: {problem}")),
+            );
+        }
+        valid_entries(&self.url, self.skip_invalid_entries, versions, problems)
+    }
+
+    fn offline(&self) -> Option<Box<dyn VersionSource>> {
+        let cache = self.cache.as_ref()?;
+        Some(Box::new(
+            VelocityIndexSource::new(&self.url)
+                .with_cache(Some(cache.offline()))
+                .with_skip_invalid_entries(self.skip_invalid_entries)
+                .with_signature(self.signature.clone()),
+        ))
+    }
+}
+
+/// 1 件分のエントリを検証する。問題はすべて集めて返す。
+fn parse_entry(
+    base: &Url,
+    version: &str,
+    raw: serde_json::Value,
+) -> Result<VersionInfo, Vec<String>> {
+    let entry: VelocityEntry =
+        serde_json::from_value(raw).map_err(|err| vec![format!("形式が不正です（{err}）")])?;
+    let mut problems = Vec::new();
+
+    let url = match location::resolve(base, &entry.url) {
+        Ok(_) if entry.url.trim().is_empty() => {
+            problems.push("url が空です".to_string());
+            None
+        }
+        Ok(url) if !matches!(url.scheme(), "http" | "https" | "file") => {
+            problems.push(format!("url のスキームに対応していません: {}", entry.url));
+            None
+        }
+        Ok(url) => Some(url),
+        Err(err) => {
+            problems.push(format!("url を解釈できません: {} ({err})", entry.url));
+            None
+        }
+    };
+    let checksums = Checksums {
+        sha512: entry.checksum.sha512,
+        sha256: entry.checksum.sha256,
+        sha1: entry.checksum.sha1,
+    };
+    problems.extend(check_entry(&checksums, &entry.kind));
+
+    match url {
+        Some(url) if problems.is_empty() => Ok(VersionInfo {
+            version: version.to_string(),
             kind: entry.kind,
-            url: entry.url,
-            sha256,
+            url: url.to_string(),
+            checksums,
             build: entry.build,
-        });
+        }),
+        _ => Err(problems),
+    }
+}
+
+/// 取得元によらないエントリの検証（チェックサムの桁数と type）。
+fn check_entry(checksums: &Checksums, kind: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (algorithm, value) in checksums.iter() {
+        if !algorithm.is_valid_hex(value) {
+            problems.push(format!(
+                "{algorithm} が {} 桁の16進数ではありません: {value}",
+                algorithm.hex_len()
+            ));
+        }
+    }
+    if checksums.is_empty() {
+        problems.push("チェックサム（sha512 / sha256 / sha1）がありません".to_string());
+    }
+    if !KNOWN_KINDS.contains(&kind) {
+        problems.push(format!(
+            "type が不明です: {kind}（{} のいずれか）",
+            KNOWN_KINDS.join(" / ")
+        ));
+    }
+    problems
+}
+
+/// 問題が無ければ一覧を返す。`skip_invalid_entries` なら問題を警告して有効なエントリだけを返す。
+fn valid_entries(
+    location: &str,
+    skip_invalid_entries: bool,
+    versions: Vec<VersionInfo>,
+    problems: Vec<String>,
+) -> Result<Vec<VersionInfo>, Box<dyn Error>> {
+    if problems.is_empty() {
+        return Ok(versions);
+    }
+    if skip_invalid_entries {
+        for problem in &problems {
+            eprintln!("警告: 不正なエントリを除外しました: {problem}");
+        }
+        return Ok(versions);
+    }
+    Err(format!(
+        "バージョン一覧に不正なエントリがあります（{} 件）: {location}\n  - {}\n--skip-invalid-entries を指定すると、不正なエントリを除外して続行します。",
+        problems.len(),
+        problems.join("\n  - ")
+    )
+    .into())
+}
+
+/// PaperMC downloads API（`/v2/projects/<project>`）。
+///
+/// バージョンごとに最新の build を 1 件だけ採用する。
+pub struct PaperMcSource {
+    project_url: String,
+    cache: Option<IndexCache>,
+    skip_invalid_entries: bool,
+    signature: Option<SignatureVerifier>,
+}
+
+impl PaperMcSource {
+    pub fn new(project_url: &str) -> Self {
+        Self {
+            project_url: project_url.trim_end_matches('/').to_string(),
+            cache: None,
+            skip_invalid_entries: false,
+            signature: None,
+        }
+    }
+
+    pub fn with_cache(mut self, cache: Option<IndexCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_skip_invalid_entries(mut self, skip: bool) -> Self {
+        self.skip_invalid_entries = skip;
+        self
+    }
+
+    /// PaperMC API は署名を提供しないため、署名が必須の場合は一覧を使わない。
+    pub fn with_signature(mut self, signature: Option<SignatureVerifier>) -> Self {
+        self.signature = signature;
+        self
     }
 
-    versions.sort_by(|a, b| b.version.cmp(&a.version));
+    /// 最新の build を返す。build や jar が無ければ、その問題を `Err` で返す。
+    fn latest_build(
+        &self,
+        client: &Client,
+        version: &str,
+    ) -> Result<Result<VersionInfo, String>, Box<dyn Error>> {
+        let builds_url = format!("{}/versions/{version}/builds", self.project_url);
+        let builds: PaperBuilds =
+            serde_json::from_str(&get_text(client, self.cache.as_ref(), &builds_url)?)?;
+        let Some(build) = builds.builds.into_iter().max_by_key(|build| build.build) else {
+            return Ok(Err("build がありません".to_string()));
+        };
+        let Some(download) = build.downloads.get("application") else {
+            return Ok(Err(format!(
+                "build {} に application のダウンロードがありません",
+                build.build
+            )));
+        };
+        Ok(Ok(VersionInfo {
+            version: version.to_string(),
+            kind: paper_channel_kind(&build.channel),
+            url: format!(
+                "{}/versions/{version}/builds/{}/downloads/{}",
+                self.project_url, build.build, download.name
+            ),
+            checksums: Checksums::sha256(&download.sha256),
+            build: Some(build.build),
+        }))
+    }
+}
+
+impl VersionSource for PaperMcSource {
+    fn location(&self) -> &str {
+        &self.project_url
+    }
+
+    fn fetch(&self, client: &Client) -> Result<Vec<VersionInfo>, Box<dyn Error>> {
+        if let Some(signature) = &self.signature {
+            signature.unsigned(&self.project_url, "PaperMC API の一覧には署名がありません")?;
+        }
+        let project: PaperProject =
+            serde_json::from_str(&get_text(client, self.cache.as_ref(), &self.project_url)?)?;
+        let mut versions = Vec::new();
+        let mut problems = Vec::new();
+        for version in &project.versions {
+            let info = match self.latest_build(client, version)? {
+                Ok(info) => info,
+                Err(problem) => {
+                    problems.push(format!("{version}: {problem}"));
+                    continue;
+                }
+            };
+            let entry_problems = check_entry(&info.checksums, &info.kind);
+            if entry_problems.is_empty() {
+                versions.push(info);
+            } else {
+                problems.extend(
+                    entry_problems
+                        .into_iter()
+                        .map(|problem| format!("{version}: {problem}")),
+                );
+            }
+        }
+        valid_entries(
+            &self.project_url,
+            self.skip_invalid_entries,
+            versions,
+            problems,
+        )
+    }
+
+    fn offline(&self) -> Option<Box<dyn VersionSource>> {
+        let cache = self.cache.as_ref()?;
+        Some(Box::new(
+            PaperMcSource::new(&self.project_url)
+                .with_cache(Some(cache.offline()))
+                .with_skip_invalid_entries(self.skip_invalid_entries)
+                .with_signature(self.signature.clone()),
+        ))
+    }
+}
+
+/// `url` の本文を取得する。
+///
+/// キャッシュがあれば ETag / Last-Modified で再検証し、変更が無ければ保存済みの本文を使う。
+/// 通信しないキャッシュでは、保存済みの本文を取得日時の警告付きで使う。
+fn get_text(
+    client: &Client,
+    cache: Option<&IndexCache>,
+    url: &str,
+) -> Result<String, Box<dyn Error>> {
+    let Some(cache) = cache else {
+        return Ok(client.get(url).send()?.error_for_status()?.text()?);
+    };
+    let cached = cache.load(url);
+    if cache.is_offline() {
+        let cached =
+            cached.ok_or_else(|| format!("キャッシュされたバージョン一覧がありません: {url}"))?;
+        eprintln!(
+            "警告: {} に取得した古いバージョン一覧を使います: {url}",
+            format_timestamp(cached.fetched_at)
+        );
+        return Ok(cached.body);
+    }
+
+    let mut request = client.get(url);
+    if let Some(cached) = &cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let response = request.send()?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        // 条件付きで要求していないのに 304 が返った場合、使える本文が無い
+        let mut cached = cached
+            .ok_or_else(|| format!("キャッシュが無いのに 304 Not Modified が返りました: {url}"))?;
+        cached.fetched_at = unix_now();
+        let _ = cache.save(&cached);
+        return Ok(cached.body);
+    }
+    let response = response.error_for_status()?;
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+    let body = response.text()?;
+    let _ = cache.save(&CachedResponse {
+        url: url.to_string(),
+        etag,
+        last_modified,
+        fetched_at: unix_now(),
+        body: body.clone(),
+    });
+    Ok(body)
+}
+
+/// 一覧の署名（`<url>.sig`）を取得する。
+///
+/// 取得できた署名は一覧と同じキャッシュに保存し、通信しないキャッシュでは保存済みの署名を使う。
+fn get_signature(
+    client: &Client,
+    cache: &IndexCache,
+    url: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let signature_url = signature::signature_location(url);
+    if cache.is_offline() {
+        let cached = cache
+            .load(&signature_url)
+            .ok_or_else(|| format!("キャッシュされた署名がありません: {signature_url}"))?;
+        return Ok(Some(cached.body));
+    }
+    let Some(body) = signature::fetch_signature(client, &signature_url)? else {
+        return Ok(None);
+    };
+    let _ = cache.save(&CachedResponse {
+        url: signature_url,
+        etag: None,
+        last_modified: None,
+        fetched_at: unix_now(),
+        body: body.clone(),
+    });
+    Ok(Some(body))
+}
+
+/// PaperMC の channel を一覧の type に合わせる。
+fn paper_channel_kind(channel: &str) -> String {
+    match channel.to_ascii_lowercase().as_str() {
+        "default" => "stable".to_string(),
+        "experimental" => "beta".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// 一覧を取得して新しい順に並べる。
+///
+/// 取得できない場合、キャッシュ済みの一覧があればそれを使う（古い可能性がある旨を警告する）。
+pub fn fetch_versions(
+    client: &Client,
+    source: &dyn VersionSource,
+) -> Result<Vec<VersionInfo>, Box<dyn Error>> {
+    match fetch_sorted(client, source) {
+        Ok(versions) => Ok(versions),
+        Err(err) => {
+            let Some(offline) = source.offline() else {
+                return Err(err);
+            };
+            eprintln!("警告: バージョン一覧を取得できませんでした: {err}");
+            fetch_sorted(client, offline.as_ref()).map_err(|_| err)
+        }
+    }
+}
+
+fn fetch_sorted(
+    client: &Client,
+    source: &dyn VersionSource,
+) -> Result<Vec<VersionInfo>, Box<dyn Error>> {
+    let mut versions = source.fetch(client)?;
+    versions.sort_by(|a, b| b.cmp_release(a));
     if versions.is_empty() {
         return Err("バージョン一覧が空です。".into());
     }
     Ok(versions)
 }
 
+/// バージョン指定を一覧から解決する。`versions` は新しい順に並んでいること。
+///
+/// - `latest` : 最新
+/// - `latest-<kind>` : kind が一致する最新（`latest-stable` など）
+/// - 完全一致するバージョン
+/// - `3.4` などの数値の接頭辞 : 一致するうちの最新
+pub fn resolve_version<'a>(versions: &'a [VersionInfo], spec: &str) -> Option<&'a VersionInfo> {
+    let spec = spec.trim();
+    if spec == "latest" {
+        return versions.first();
+    }
+    if let Some(kind) = spec.strip_prefix("latest-") {
+        return versions.iter().find(|version| version.kind == kind);
+    }
+    if let Some(exact) = versions.iter().find(|version| version.version == spec) {
+        return Some(exact);
+    }
+    let prefix = VelocityVersion::parse(spec);
+    if prefix.pre_release.is_some() {
+        return None;
+    }
+    versions
+        .iter()
+        .find(|version| VelocityVersion::parse(&version.version).starts_with(&prefix.numbers))
+}
+
 #[cfg(test)]
+#[allow(clippy::err_expect)]
 mod tests {
     use super::*;
     use httpmock::Method::GET;
@@ -106,7 +819,8 @@ mod tests {
         });
 
         let client = Client::builder().build().expect("client");
-        let result = fetch_versions(&client, &server.url("/velocity.json"));
+        let source = VelocityIndexSource::new(&server.url("/velocity.json"));
+        let result = fetch_versions(&client, &source);
         assert!(result.is_err());
         let message = result.err().expect("error").to_string();
         assert!(message.contains("status=error"));
@@ -134,7 +848,8 @@ mod tests {
         });
 
         let client = Client::builder().build().expect("client");
-        let result = fetch_versions(&client, &server.url("/velocity.json"));
+        let source = VelocityIndexSource::new(&server.url("/velocity.json"));
+        let result = fetch_versions(&client, &source);
         assert!(result.is_err());
         let message = result.err().expect("error").to_string();
         assert!(message.contains("sha256"));
@@ -153,9 +868,505 @@ mod tests {
         });
 
         let client = Client::builder().build().expect("client");
-        let result = fetch_versions(&client, &server.url("/velocity.json"));
+        let source = VelocityIndexSource::new(&server.url("/velocity.json"));
+        let result = fetch_versions(&client, &source);
         assert!(result.is_err());
         let message = result.err().expect("error").to_string();
         assert!(message.contains("バージョン一覧が空です"));
     }
+
+    fn version_info(version: &str, kind: &str, build: Option<u64>) -> VersionInfo {
+        VersionInfo {
+            version: version.to_string(),
+            kind: kind.to_string(),
+            url: format!("http://example.invalid/velocity-proxy-{version}.jar"),
+            checksums: Checksums::default(),
+            build,
+        }
+    }
+
+    fn sorted(mut versions: Vec<VersionInfo>) -> Vec<VersionInfo> {
+        versions.sort_by(|a, b| b.cmp_release(a));
+        versions
+    }
+
+    #[test]
+    fn cmp_release_orders_numerically_and_by_build() {
+        let versions = sorted(vec![
+            version_info("3.9.0", "stable", Some(5)),
+            version_info("3.10.0-SNAPSHOT", "beta", Some(1)),
+            version_info("3.10.0", "stable", Some(2)),
+            version_info("3.9.0", "stable", Some(7)),
+        ]);
+        let order: Vec<_> = versions
+            .iter()
+            .map(|version| (version.version.as_str(), version.build))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("3.10.0", Some(2)),
+                ("3.10.0-SNAPSHOT", Some(1)),
+                ("3.9.0", Some(7)),
+                ("3.9.0", Some(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_version_handles_aliases() {
+        let versions = sorted(vec![
+            version_info("3.3.0-SNAPSHOT", "stable", Some(436)),
+            version_info("3.4.0-SNAPSHOT", "beta", Some(500)),
+            version_info("3.4.0", "stable", Some(10)),
+            version_info("3.5.0-SNAPSHOT", "beta", Some(1)),
+        ]);
+
+        let resolve = |spec: &str| resolve_version(&versions, spec).map(|v| v.version.as_str());
+        assert_eq!(resolve("latest"), Some("3.5.0-SNAPSHOT"));
+        assert_eq!(resolve("latest-stable"), Some("3.4.0"));
+        assert_eq!(resolve("latest-beta"), Some("3.5.0-SNAPSHOT"));
+        assert_eq!(resolve("3.4"), Some("3.4.0"));
+        assert_eq!(resolve("3.3"), Some("3.3.0-SNAPSHOT"));
+        assert_eq!(resolve("3.4.0-SNAPSHOT"), Some("3.4.0-SNAPSHOT"));
+        assert_eq!(resolve("3.6"), None);
+        assert_eq!(resolve("3.6.0-SNAPSHOT"), None);
+    }
+
+    #[test]
+    fn papermc_source_uses_latest_build_per_version() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/v2/projects/velocity");
+            then.status(200).body(
+                r#"{"project_id":"velocity","project_name":"Velocity","versions":["3.3.0-SNAPSHOT","3.4.0-SNAPSHOT"]}"#,
+            );
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/v2/projects/velocity/versions/3.3.0-SNAPSHOT/builds");
+            then.status(200).body(
+                r#"{"version":"3.3.0-SNAPSHOT","builds":[
+  {"build":430,"channel":"default","downloads":{"application":{"name":"velocity-3.3.0-SNAPSHOT-430.jar","sha256":"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"}}},
+  {"build":436,"channel":"default","downloads":{"application":{"name":"velocity-3.3.0-SNAPSHOT-436.jar","sha256":"bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"}}}
+]}"#,
+            );
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/v2/projects/velocity/versions/3.4.0-SNAPSHOT/builds");
+            then.status(200).body(
+                r#"{"version":"3.4.0-SNAPSHOT","builds":[
+  {"build":500,"channel":"experimental","downloads":{"application":{"name":"velocity-3.4.0-SNAPSHOT-500.jar","sha256":"cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc"}}}
+]}"#,
+            );
+        });
+
+        let client = Client::builder().build().expect("client");
+        let project_url = server.url("/v2/projects/velocity");
+        assert_eq!(SourceKind::detect(&project_url), SourceKind::PaperMc);
+        let source =
+            SourceKind::detect(&project_url).source(&project_url, &SourceOptions::default());
+        let versions = fetch_versions(&client, source.as_ref()).expect("versions");
+
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].version, "3.4.0-SNAPSHOT");
+        assert_eq!(versions[0].kind, "beta");
+        assert_eq!(versions[0].build, Some(500));
+        assert_eq!(versions[1].version, "3.3.0-SNAPSHOT");
+        assert_eq!(versions[1].kind, "stable");
+        assert_eq!(versions[1].build, Some(436));
+        assert_eq!(
+            versions[1].checksums.sha256.as_deref(),
+            Some("bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb")
+        );
+        assert_eq!(
+            versions[1].url,
+            format!(
+                "{project_url}/versions/3.3.0-SNAPSHOT/builds/436/downloads/velocity-3.3.0-SNAPSHOT-436.jar"
+            )
+        );
+    }
+
+    #[test]
+    fn papermc_source_checks_entries() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/v2/projects/velocity");
+            then.status(200)
+                .body(r#"{"versions":["3.3.0-SNAPSHOT","3.4.0-SNAPSHOT"]}"#);
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/v2/projects/velocity/versions/3.3.0-SNAPSHOT/builds");
+            then.status(200).body(
+                r#"{"builds":[{"build":436,"channel":"default","downloads":{"application":{"name":"velocity-436.jar","sha256":"abcd"}}}]}"#,
+            );
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/v2/projects/velocity/versions/3.4.0-SNAPSHOT/builds");
+            then.status(200).body(format!(
+                r#"{{"builds":[{{"build":500,"channel":"alpha","downloads":{{"application":{{"name":"velocity-500.jar","sha256":"{}"}}}}}}]}}"#,
+                "c".repeat(64)
+            ));
+        });
+
+        let client = Client::builder().build().expect("client");
+        let source = PaperMcSource::new(&server.url("/v2/projects/velocity"));
+        let message = fetch_versions(&client, &source)
+            .expect_err("error")
+            .to_string();
+        assert!(message.contains("不正なエントリがあります（2 件）"));
+        assert!(message.contains("3.3.0-SNAPSHOT: sha256 が 64 桁の16進数ではありません: abcd"));
+        assert!(message.contains("3.4.0-SNAPSHOT: type が不明です: alpha"));
+
+        let source = source.with_skip_invalid_entries(true);
+        assert!(fetch_versions(&client, &source).is_err());
+    }
+
+    #[test]
+    fn papermc_source_reports_versions_without_jar() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/v2/projects/velocity");
+            then.status(200)
+                .body(r#"{"versions":["3.2.0-SNAPSHOT","3.3.0-SNAPSHOT","3.4.0-SNAPSHOT"]}"#);
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/v2/projects/velocity/versions/3.2.0-SNAPSHOT/builds");
+            then.status(200).body(r#"{"builds":[]}"#);
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/v2/projects/velocity/versions/3.3.0-SNAPSHOT/builds");
+            then.status(200)
+                .body(r#"{"builds":[{"build":436,"channel":"default","downloads":{}}]}"#);
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/v2/projects/velocity/versions/3.4.0-SNAPSHOT/builds");
+            then.status(200).body(format!(
+                r#"{{"builds":[{{"build":500,"channel":"default","downloads":{{"application":{{"name":"velocity-500.jar","sha256":"{}"}}}}}}]}}"#,
+                "c".repeat(64)
+            ));
+        });
+
+        let client = Client::builder().build().expect("client");
+        let source = PaperMcSource::new(&server.url("/v2/projects/velocity"));
+        let message = fetch_versions(&client, &source)
+            .expect_err("error")
+            .to_string();
+        assert!(message.contains("不正なエントリがあります（2 件）"));
+        assert!(message.contains("3.2.0-SNAPSHOT: build がありません"));
+        assert!(
+            message.contains("3.3.0-SNAPSHOT: build 436 に application のダウンロードがありません")
+        );
+
+        let source = source.with_skip_invalid_entries(true);
+        let versions = fetch_versions(&client, &source).expect("versions");
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].version, "3.4.0-SNAPSHOT");
+    }
+
+    #[test]
+    fn velocity_index_source_reads_local_paths_and_resolves_relative_urls() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let mirror = temp_dir.path().join("mirror");
+        std::fs::create_dir_all(mirror.join("jars")).expect("create mirror");
+        let index_path = mirror.join("velocity.json");
+        std::fs::write(
+            &index_path,
+            r#"{
+  "status": "ok",
+  "data": {
+    "3.4.0": {
+      "url": "jars/velocity-proxy-3.4.0.jar",
+      "checksum": { "sha256": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" },
+      "type": "stable"
+    },
+    "3.3.0": {
+      "url": "https://example.invalid/velocity-proxy-3.3.0.jar",
+      "checksum": { "sha256": "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb" },
+      "type": "stable"
+    }
+  }
+}"#,
+        )
+        .expect("write index");
+
+        let client = Client::builder().build().expect("client");
+        let expected = location::file_url(&mirror.join("jars").join("velocity-proxy-3.4.0.jar"))
+            .expect("file url")
+            .to_string();
+        let file_url = location::file_url(&index_path)
+            .expect("file url")
+            .to_string();
+        for index_location in [index_path.to_string_lossy().to_string(), file_url] {
+            let source = VelocityIndexSource::new(&index_location);
+            let versions = fetch_versions(&client, &source).expect("versions");
+            assert_eq!(versions[0].url, expected);
+            assert_eq!(
+                versions[1].url,
+                "https://example.invalid/velocity-proxy-3.3.0.jar"
+            );
+        }
+    }
+
+    #[test]
+    fn velocity_index_source_resolves_relative_urls_against_http_index() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/mirror/velocity.json");
+            then.status(200).body(
+                r#"{
+  "status": "ok",
+  "data": {
+    "3.4.0": {
+      "url": "jars/velocity-proxy-3.4.0.jar",
+      "checksum": { "sha256": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" },
+      "type": "stable"
+    }
+  }
+}"#,
+            );
+        });
+
+        let client = Client::builder().build().expect("client");
+        let source = VelocityIndexSource::new(&server.url("/mirror/velocity.json"));
+        let versions = fetch_versions(&client, &source).expect("versions");
+        assert_eq!(
+            versions[0].url,
+            server.url("/mirror/jars/velocity-proxy-3.4.0.jar")
+        );
+    }
+
+    #[test]
+    fn cached_index_is_revalidated_with_etag() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let cache = IndexCache::in_cache_dir(temp_dir.path());
+        let server = MockServer::start();
+        let body = r#"{
+  "status": "ok",
+  "data": {
+    "3.4.0": {
+      "url": "http://example.invalid/velocity-proxy-3.4.0.jar",
+      "checksum": { "sha256": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" },
+      "build": 1,
+      "type": "stable"
+    }
+  }
+}"#;
+        let not_modified = server.mock(|when, then| {
+            when.method(GET)
+                .path("/velocity.json")
+                .header("if-none-match", "\"v1\"");
+            then.status(304);
+        });
+        let full = server.mock(|when, then| {
+            when.method(GET).path("/velocity.json");
+            then.status(200).header("etag", "\"v1\"").body(body);
+        });
+        let client = Client::builder().build().expect("client");
+        let source =
+            VelocityIndexSource::new(&server.url("/velocity.json")).with_cache(Some(cache));
+
+        for _ in 0..2 {
+            let versions = fetch_versions(&client, &source).expect("versions");
+            assert_eq!(versions[0].version, "3.4.0");
+        }
+        full.assert_hits(1);
+        not_modified.assert_hits(1);
+    }
+
+    #[test]
+    fn cached_index_is_used_when_unavailable() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let cache = IndexCache::in_cache_dir(temp_dir.path());
+        let server = MockServer::start();
+        let body = r#"{
+  "status": "ok",
+  "data": {
+    "3.4.0": {
+      "url": "http://example.invalid/velocity-proxy-3.4.0.jar",
+      "checksum": { "sha256": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" },
+      "build": 1,
+      "type": "stable"
+    }
+  }
+}"#;
+        let mut available = server.mock(|when, then| {
+            when.method(GET).path("/velocity.json");
+            then.status(200).body(body);
+        });
+        let client = Client::builder().build().expect("client");
+        let source =
+            VelocityIndexSource::new(&server.url("/velocity.json")).with_cache(Some(cache.clone()));
+        fetch_versions(&client, &source).expect("versions");
+
+        available.delete();
+        server.mock(|when, then| {
+            when.method(GET).path("/velocity.json");
+            then.status(503);
+        });
+        let versions = fetch_versions(&client, &source).expect("stale versions");
+        assert_eq!(versions[0].version, "3.4.0");
+
+        let uncached = VelocityIndexSource::new(&server.url("/velocity.json"));
+        assert!(fetch_versions(&client, &uncached).is_err());
+    }
+
+    #[test]
+    fn not_modified_without_cached_body_is_an_error() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let cache = IndexCache::in_cache_dir(temp_dir.path());
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/velocity.json");
+            then.status(304);
+        });
+        let client = Client::builder().build().expect("client");
+        let source =
+            VelocityIndexSource::new(&server.url("/velocity.json")).with_cache(Some(cache));
+        let message = fetch_versions(&client, &source)
+            .expect_err("error")
+            .to_string();
+        assert!(message.contains("304 Not Modified"), "{message}");
+    }
+
+    #[test]
+    fn cached_signature_is_used_when_unavailable() {
+        use base64::Engine;
+        use base64::engine::general_purpose::STANDARD;
+        use ed25519_dalek::{Signer, SigningKey};
+
+        use crate::config::SignatureConfig;
+
+        let temp_dir = tempfile::TempDir::new().expect("temp dir");
+        let cache = IndexCache::in_cache_dir(temp_dir.path());
+        let server = MockServer::start();
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let body = r#"{
+  "status": "ok",
+  "data": {
+    "3.4.0": {
+      "url": "http://example.invalid/velocity-proxy-3.4.0.jar",
+      "checksum": { "sha256": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" },
+      "build": 1,
+      "type": "stable"
+    }
+  }
+}"#;
+        let mut available = server.mock(|when, then| {
+            when.method(GET).path("/velocity.json");
+            then.status(200).body(body);
+        });
+        let mut signed = server.mock(|when, then| {
+            when.method(GET).path("/velocity.json.sig");
+            then.status(200)
+                .body(STANDARD.encode(key.sign(body.as_bytes()).to_bytes()));
+        });
+        let verifier = SignatureVerifier::from_config(&SignatureConfig {
+            keys: Some(vec![STANDARD.encode(key.verifying_key().to_bytes())]),
+            required: Some(true),
+        })
+        .expect("config");
+        let client = Client::builder().build().expect("client");
+        let source = VelocityIndexSource::new(&server.url("/velocity.json"))
+            .with_cache(Some(cache))
+            .with_signature(verifier);
+        fetch_versions(&client, &source).expect("versions");
+
+        available.delete();
+        signed.delete();
+        server.mock(|when, then| {
+            when.method(GET);
+            then.status(503);
+        });
+        let versions = fetch_versions(&client, &source).expect("stale versions");
+        assert_eq!(versions[0].version, "3.4.0");
+    }
+
+    const INVALID_INDEX: &str = r#"{
+  "status": "ok",
+  "data": {
+    "3.4.0": {
+      "url": "velocity-proxy-3.4.0.jar",
+      "checksum": { "sha256": "dddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddd" },
+      "build": 4,
+      "type": "stable"
+    },
+    "3.3.0": {
+      "url": "ftp://example.invalid/velocity-proxy-3.3.0.jar",
+      "checksum": { "sha256": "abcd" },
+      "build": 3,
+      "type": "nightly"
+    },
+    "3.2.0": {
+      "checksum": { "sha256": null },
+      "type": "stable"
+    },
+    "3.1.4": {
+      "url": "",
+      "checksum": { "sha256": "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff" },
+      "build": 2,
+      "type": "stable"
+    },
+    "3.4.0": {
+      "url": "velocity-proxy-3.4.0-again.jar",
+      "checksum": { "sha256": "eeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee" },
+      "build": 5,
+      "type": "stable"
+    }
+  }
+}"#;
+
+    #[test]
+    fn fetch_versions_reports_every_invalid_entry() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/velocity.json");
+            then.status(200).body(INVALID_INDEX);
+        });
+
+        let client = Client::builder().build().expect("client");
+        let source = VelocityIndexSource::new(&server.url("/velocity.json"));
+        let message = fetch_versions(&client, &source)
+            .expect_err("error")
+            .to_string();
+
+        assert!(message.contains("不正なエントリがあります（6 件）"));
+        assert!(message.contains("3.3.0: url のスキームに対応していません"));
+        assert!(message.contains("3.3.0: sha256 が 64 桁の16進数ではありません: abcd"));
+        assert!(message.contains("3.3.0: type が不明です: nightly"));
+        assert!(message.contains("3.2.0: 形式が不正です"));
+        assert!(message.contains("3.4.0: 同じバージョンのエントリが重複しています（build 4）"));
+        assert!(message.contains("3.1.4: url が空です"));
+        assert!(message.contains("--skip-invalid-entries"));
+    }
+
+    #[test]
+    fn fetch_versions_can_skip_invalid_entries() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/velocity.json");
+            then.status(200).body(INVALID_INDEX.replace(
+                "\"3.4.0\": {\n      \"url\": \"velocity-proxy-3.4.0-again.jar\"",
+                "\"3.1.0\": {\n      \"url\": \"velocity-proxy-3.1.0.jar\"",
+            ));
+        });
+
+        let client = Client::builder().build().expect("client");
+        let source =
+            VelocityIndexSource::new(&server.url("/velocity.json")).with_skip_invalid_entries(true);
+        let versions = fetch_versions(&client, &source).expect("versions");
+
+        let names: Vec<_> = versions
+            .iter()
+            .map(|version| version.version.as_str())
+            .collect();
+        assert_eq!(names, vec!["3.4.0", "3.1.0"]);
+        assert_eq!(versions[0].url, server.url("/velocity-proxy-3.4.0.jar"));
+    }
 }
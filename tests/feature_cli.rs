@@ -1,9 +1,19 @@
 use std::process::Command;
 
+use httpmock::Method::GET;
+use httpmock::MockServer;
+
 fn bin_path() -> &'static str {
     env!("CARGO_BIN_EXE_mc-velocity-installer")
 }
 
+/// 利用者のキャッシュを使わないように実行する。
+fn installer() -> Command {
+    let mut command = Command::new(bin_path());
+    command.env("MC_VELOCITY_NO_CACHE", "1");
+    command
+}
+
 #[test]
 fn help_shows_usage() {
     let output = Command::new(bin_path())
@@ -16,6 +26,9 @@ fn help_shows_usage() {
     assert!(stdout.contains("使い方"));
     assert!(stdout.contains("--deploy"));
     assert!(stdout.contains("--redownload-jar"));
+    assert!(stdout.contains("--non-interactive"));
+    assert!(stdout.contains("終了コード:\n  0  成功"));
+    assert!(stdout.contains("  2  check-updates で更新がある"));
 }
 
 #[test]
@@ -29,3 +42,329 @@ fn version_shows_version() {
     let stdout = String::from_utf8_lossy(&output.stdout);
     assert!(stdout.contains(env!("CARGO_PKG_VERSION")));
 }
+
+#[test]
+fn version_with_value_is_not_build_version() {
+    let output = installer()
+        .args(["--non-interactive", "--version", "3.4.0"])
+        .output()
+        .expect("run binary");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--dir"));
+}
+
+#[test]
+fn version_without_value_among_install_flags_is_rejected() {
+    for args in [
+        &["--dir", "velocity", "--version", "--yes"][..],
+        &["--dir", "velocity", "--version", "-y"][..],
+    ] {
+        let output = installer().args(args).output().expect("run binary");
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("--version の値が必要です"), "{stderr}");
+    }
+}
+
+#[test]
+fn option_values_may_start_with_single_dash() {
+    let temp_dir = tempfile::TempDir::new().expect("temp dir");
+    let output = installer()
+        .args(["info", "--dir", "-old"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("run binary");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("値が必要です"), "{stderr}");
+    assert!(stderr.contains(": -old"), "{stderr}");
+}
+
+#[test]
+fn list_versions_outputs_json() {
+    let server = MockServer::start();
+    let body = r#"{
+  "status": "ok",
+  "data": {
+    "3.9.0": {
+      "url": "http://example.invalid/velocity-proxy-3.9.0.jar",
+      "checksum": { "sha1": null, "sha256": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" },
+      "build": 1,
+      "type": "stable"
+    },
+    "3.10.0": {
+      "url": "http://example.invalid/velocity-proxy-3.10.0.jar",
+      "checksum": { "sha1": null, "sha256": "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb" },
+      "build": 2,
+      "type": "stable"
+    },
+    "3.11.0-SNAPSHOT": {
+      "url": "http://example.invalid/velocity-proxy-3.11.0-SNAPSHOT.jar",
+      "checksum": { "sha1": null, "sha256": "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc" },
+      "build": 3,
+      "type": "beta"
+    }
+  }
+}"#;
+    server.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200).body(body);
+    });
+
+    let output = installer()
+        .args([
+            "list-versions",
+            "--kind",
+            "stable",
+            "--limit",
+            "1",
+            "--format",
+            "json",
+        ])
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .output()
+        .expect("run binary");
+
+    assert!(output.status.success());
+    let versions: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("parse json output");
+    let versions = versions.as_array().expect("array");
+    assert_eq!(versions.len(), 1);
+    assert_eq!(versions[0]["version"], "3.10.0");
+    assert_eq!(versions[0]["kind"], "stable");
+    assert_eq!(versions[0]["build"], 2);
+    assert_eq!(versions[0]["sha256"], "b".repeat(64));
+    assert_eq!(
+        versions[0]["url"],
+        "http://example.invalid/velocity-proxy-3.10.0.jar"
+    );
+}
+
+#[test]
+fn list_versions_rejects_unknown_format() {
+    let output = installer()
+        .args(["list-versions", "--format", "yaml"])
+        .output()
+        .expect("run binary");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--format"));
+}
+
+const SINGLE_VERSION_INDEX: &str = r#"{
+  "status": "ok",
+  "data": {
+    "3.4.0": {
+      "url": "http://example.invalid/velocity-proxy-3.4.0.jar",
+      "checksum": { "sha1": null, "sha256": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" },
+      "build": 1,
+      "type": "stable"
+    }
+  }
+}"#;
+
+#[test]
+fn list_versions_sends_versioned_user_agent() {
+    let server = MockServer::start();
+    let user_agent = format!("mc-velocity-installer/{}", env!("CARGO_PKG_VERSION"));
+    let mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/velocity.json")
+            .header("user-agent", user_agent.as_str());
+        then.status(200).body(SINGLE_VERSION_INDEX);
+    });
+    let config_home = tempfile::TempDir::new().expect("tempdir");
+
+    let output = installer()
+        .arg("list-versions")
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .output()
+        .expect("run binary");
+
+    assert!(output.status.success());
+    mock.assert();
+}
+
+#[test]
+fn config_file_is_overridden_by_env_and_flags() {
+    let server = MockServer::start();
+    let from_flag = server.mock(|when, then| {
+        when.method(GET)
+            .path("/velocity.json")
+            .header("user-agent", "from-flag");
+        then.status(200).body(SINGLE_VERSION_INDEX);
+    });
+    let from_env = server.mock(|when, then| {
+        when.method(GET)
+            .path("/velocity.json")
+            .header("user-agent", "from-env");
+        then.status(200).body(SINGLE_VERSION_INDEX);
+    });
+    let from_file = server.mock(|when, then| {
+        when.method(GET)
+            .path("/velocity.json")
+            .header("user-agent", "from-file");
+        then.status(200).body(SINGLE_VERSION_INDEX);
+    });
+    let config_dir = tempfile::TempDir::new().expect("tempdir");
+    let config_path = config_dir.path().join("config.toml");
+    std::fs::write(&config_path, "[http]\nuser_agent = \"from-file\"\n").expect("write config");
+
+    let run = |extra_args: &[&str], env_agent: Option<&str>| {
+        let mut command = installer();
+        command
+            .arg("list-versions")
+            .args(extra_args)
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .env("MC_VELOCITY_CONFIG", &config_path);
+        if let Some(agent) = env_agent {
+            command.env("MC_VELOCITY_USER_AGENT", agent);
+        }
+        let output = command.output().expect("run binary");
+        assert!(output.status.success());
+    };
+    run(&[], None);
+    run(&[], Some("from-env"));
+    run(&["--user-agent", "from-flag"], Some("from-env"));
+
+    from_file.assert_hits(1);
+    from_env.assert_hits(1);
+    from_flag.assert_hits(1);
+}
+
+#[test]
+fn explicit_config_file_must_exist() {
+    let output = installer()
+        .args(["list-versions", "--config", "/nonexistent/config.toml"])
+        .output()
+        .expect("run binary");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("設定ファイルが見つかりません"));
+}
+
+#[test]
+fn list_versions_goes_through_explicit_proxy() {
+    let proxy = MockServer::start();
+    let mock = proxy.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200).body(SINGLE_VERSION_INDEX);
+    });
+
+    let output = installer()
+        .args(["list-versions", "--proxy", &proxy.base_url()])
+        .env(
+            "MC_VELOCITY_INDEX_URL",
+            "http://velocity-index.invalid/velocity.json",
+        )
+        .output()
+        .expect("run binary");
+
+    assert!(output.status.success());
+    mock.assert();
+}
+
+#[test]
+fn list_versions_times_out() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200)
+            .delay(std::time::Duration::from_secs(5))
+            .body(SINGLE_VERSION_INDEX);
+    });
+
+    let output = installer()
+        .args(["list-versions", "--timeout", "1"])
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .output()
+        .expect("run binary");
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn list_versions_falls_back_to_index_mirror() {
+    let origin = MockServer::start();
+    let origin_mock = origin.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(503);
+    });
+    let broken = MockServer::start();
+    broken.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200).body("not json");
+    });
+    let mirror = MockServer::start();
+    mirror.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200).body(SINGLE_VERSION_INDEX);
+    });
+
+    let output = installer()
+        .args(["list-versions", "--format", "json"])
+        .env("MC_VELOCITY_INDEX_URL", origin.url("/velocity.json"))
+        .env(
+            "MC_VELOCITY_INDEX_MIRRORS",
+            format!(
+                "{},{}",
+                broken.url("/velocity.json"),
+                mirror.url("/velocity.json")
+            ),
+        )
+        .output()
+        .expect("run binary");
+
+    assert!(output.status.success());
+    origin_mock.assert();
+    let versions: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("parse json output");
+    assert_eq!(versions[0]["version"], "3.4.0");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!(
+        "バージョン一覧の取得元: {}",
+        mirror.url("/velocity.json")
+    )));
+}
+
+#[test]
+fn list_versions_warns_when_using_stale_index() {
+    let server = MockServer::start();
+    let mut available = server.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200)
+            .header("last-modified", "Mon, 01 Jan 2024 00:00:00 GMT")
+            .body(SINGLE_VERSION_INDEX);
+    });
+    let cache_dir = tempfile::TempDir::new().expect("tempdir");
+    let run = || {
+        Command::new(bin_path())
+            .args(["list-versions", "--format", "json"])
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .env("MC_VELOCITY_CACHE_DIR", cache_dir.path())
+            .output()
+            .expect("run binary")
+    };
+    assert!(run().status.success());
+
+    available.delete();
+    server.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(502);
+    });
+    let output = run();
+
+    assert!(output.status.success());
+    let versions: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("parse json output");
+    assert_eq!(versions[0]["version"], "3.4.0");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("に取得した古いバージョン一覧を使います"));
+}
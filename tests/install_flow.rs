@@ -2,15 +2,28 @@ use std::io::Write;
 use std::path::Path;
 use std::process::{Command, Stdio};
 
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ed25519_dalek::{Signer, SigningKey};
 use httpmock::Method::GET;
 use httpmock::MockServer;
-use sha2::{Digest, Sha256};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
 use tempfile::TempDir;
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
 
 fn bin_path() -> &'static str {
     env!("CARGO_BIN_EXE_mc-velocity-installer")
 }
 
+/// 利用者の jar キャッシュを使わず、テスト間でも共有しないように実行する。
+fn installer() -> Command {
+    let mut command = Command::new(bin_path());
+    command.env("MC_VELOCITY_NO_CACHE", "1");
+    command
+}
+
 fn assert_systemd_service(install_dir: &Path) {
     let service_path = install_dir.join("velocity.service");
     assert!(service_path.exists());
@@ -32,6 +45,48 @@ fn assert_systemd_service(install_dir: &Path) {
     assert!(!contents.contains("StandardError="));
 }
 
+fn mock_single_version(server: &MockServer, version: &str, jar_name: &str) {
+    mock_versions(server, &[(version, "stable", jar_name)]);
+}
+
+fn mock_versions(server: &MockServer, versions: &[(&str, &str, &str)]) {
+    let jar_bytes = b"velocity-jar";
+    let sha256 = format!("{:x}", Sha256::digest(jar_bytes));
+    let mut entries = Vec::new();
+    for (build, (version, kind, jar_name)) in versions.iter().enumerate() {
+        let jar_path = format!("/{}", jar_name);
+        server.mock(|when, then| {
+            when.method(GET).path(jar_path.as_str());
+            then.status(200).body(jar_bytes.as_slice());
+        });
+        entries.push(format!(
+            r#"    "{}": {{
+      "url": "{}",
+      "checksum": {{
+        "sha1": null,
+        "sha256": "{}"
+      }},
+      "build": {},
+      "type": "{}"
+    }}"#,
+            version,
+            server.url(jar_path.as_str()),
+            sha256,
+            build + 1,
+            kind
+        ));
+    }
+
+    let index_body = format!(
+        "{{\n  \"status\": \"ok\",\n  \"data\": {{\n{}\n  }}\n}}",
+        entries.join(",\n")
+    );
+    server.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200).body(index_body);
+    });
+}
+
 #[test]
 fn install_flow_downloads_jar_and_writes_scripts() {
     let temp_dir = TempDir::new().expect("temp dir");
@@ -71,7 +126,7 @@ fn install_flow_downloads_jar_and_writes_scripts() {
         then.status(200).body(index_body);
     });
 
-    let mut child = Command::new(bin_path())
+    let mut child = installer()
         .current_dir(temp_dir.path())
         .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
         .stdin(Stdio::piped())
@@ -152,7 +207,7 @@ fn install_flow_retries_inputs_and_overwrites_existing_dir() {
         then.status(200).body(index_body);
     });
 
-    let mut child = Command::new(bin_path())
+    let mut child = installer()
         .current_dir(temp_dir.path())
         .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
         .stdin(Stdio::piped())
@@ -163,7 +218,7 @@ fn install_flow_retries_inputs_and_overwrites_existing_dir() {
 
     let inputs = [
         "custom", "n", "", "y", "maybe", "y", "x", "2", "", "n", "", "y", "128M", "256M", "n", "",
-        "", "y", "",
+        "", "y", "", "",
     ];
     let input_blob = inputs.join("\n") + "\n";
     child
@@ -226,7 +281,7 @@ fn redownload_jar_only_downloads_jar() {
         then.status(200).body(index_body);
     });
 
-    let mut child = Command::new(bin_path())
+    let mut child = installer()
         .arg("--redownload-jar")
         .current_dir(temp_dir.path())
         .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
@@ -311,7 +366,7 @@ fn redownload_jar_can_replace_start_scripts() {
     )
     .expect("write start.bat");
 
-    let mut child = Command::new(bin_path())
+    let mut child = installer()
         .arg("--redownload-jar")
         .current_dir(temp_dir.path())
         .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
@@ -389,14 +444,13 @@ fn deploy_copies_assets_and_updates_service() {
     std::fs::write(install_dir.join("velocity.service"), service_contents)
         .expect("write velocity.service");
 
-    std::fs::write(install_dir.join("velocity.toml"), "source")
-        .expect("write velocity.toml");
+    std::fs::write(install_dir.join("velocity.toml"), "source").expect("write velocity.toml");
 
     let deploy_dir = temp_dir.path().join("deploy");
     std::fs::create_dir_all(&deploy_dir).expect("create deploy dir");
     std::fs::write(deploy_dir.join("velocity.toml"), "dest").expect("write dest toml");
 
-    let mut child = Command::new(bin_path())
+    let mut child = installer()
         .arg("--deploy")
         .arg(&deploy_dir)
         .current_dir(temp_dir.path())
@@ -432,7 +486,1748 @@ fn deploy_copies_assets_and_updates_service() {
     }
     assert!(deploy_dir.join(jar_name).exists());
     assert_systemd_service(&deploy_dir);
+    let state: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(deploy_dir.join(".mc-velocity-installer.json"))
+            .expect("read state"),
+    )
+    .expect("state json");
+    assert_eq!(state["jar"], jar_name);
+    assert_eq!(
+        state["service"]["working_directory"],
+        deploy_dir.display().to_string()
+    );
+    assert!(state["deployed_from"].is_string());
+    let installed_at = state["installed_at"].as_u64().expect("installed_at");
+    assert!(installed_at > 0 && Some(installed_at) <= state["updated_at"].as_u64());
     let toml_contents =
         std::fs::read_to_string(deploy_dir.join("velocity.toml")).expect("read toml");
     assert_eq!(toml_contents, "source");
 }
+
+#[test]
+fn non_interactive_install_uses_flags() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    let jar_name = "velocity-proxy-1.0.0.jar";
+    mock_single_version(&server, "1.0.0", jar_name);
+
+    let install_dir = temp_dir.path().join("proxy");
+    std::fs::create_dir_all(&install_dir).expect("create install dir");
+    std::fs::write(install_dir.join("existing.txt"), "data").expect("write file");
+
+    let output = installer()
+        .args([
+            "--non-interactive",
+            "--dir",
+            "proxy",
+            "--version",
+            "1.0.0",
+            "--xms",
+            "1G",
+            "--xmx=2G",
+            "--overwrite",
+            "--yes",
+        ])
+        .current_dir(temp_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert!(install_dir.join(jar_name).exists());
+    let sh = std::fs::read_to_string(install_dir.join("start.sh")).expect("read start.sh");
+    assert!(sh.contains("-Xms1G -Xmx2G"));
+    assert!(sh.contains(jar_name));
+    assert_systemd_service(&install_dir);
+}
+
+#[test]
+fn non_interactive_install_reports_missing_answer() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    mock_single_version(&server, "1.0.0", "velocity-proxy-1.0.0.jar");
+
+    let output = installer()
+        .args([
+            "--non-interactive",
+            "--dir",
+            "velocity",
+            "--version",
+            "1.0.0",
+        ])
+        .current_dir(temp_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--xms"), "stderr: {stderr}");
+    assert!(!temp_dir.path().join("velocity").exists());
+}
+
+#[test]
+fn non_interactive_install_refuses_existing_files_without_overwrite() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let install_dir = temp_dir.path().join("velocity");
+    std::fs::create_dir_all(&install_dir).expect("create install dir");
+    std::fs::write(install_dir.join("existing.txt"), "data").expect("write file");
+
+    let output = installer()
+        .args(["--non-interactive", "--dir", "velocity", "--yes"])
+        .current_dir(temp_dir.path())
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--overwrite"), "stderr: {stderr}");
+}
+
+#[test]
+fn non_interactive_redownload_replaces_scripts() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    let jar_name = "velocity-proxy-1.1.0.jar";
+    mock_single_version(&server, "1.1.0", jar_name);
+
+    let install_dir = temp_dir.path().join("velocity");
+    std::fs::create_dir_all(&install_dir).expect("create install dir");
+    std::fs::write(
+        install_dir.join("start.sh"),
+        "#!/usr/bin/env sh\nexec java -Xms512M -Xmx1G -jar \"old.jar\"\n",
+    )
+    .expect("write start.sh");
+
+    let output = installer()
+        .args([
+            "--redownload-jar",
+            "--non-interactive",
+            "--dir",
+            "velocity",
+            "--version",
+            "1.1.0",
+            "--overwrite",
+            "--replace-scripts",
+            "--yes",
+        ])
+        .current_dir(temp_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert!(install_dir.join(jar_name).exists());
+    let sh_contents = std::fs::read_to_string(install_dir.join("start.sh")).expect("read sh");
+    assert!(sh_contents.contains("-Xms512M"));
+    assert!(sh_contents.contains("-Xmx1G"));
+    assert!(sh_contents.contains(jar_name));
+}
+
+#[test]
+fn answers_file_records_and_replays_install() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    let jar_name = "velocity-proxy-1.0.0.jar";
+    mock_single_version(&server, "1.0.0", jar_name);
+    let answers_path = temp_dir.path().join("answers.json");
+
+    let mut child = installer()
+        .arg("--save-answers")
+        .arg(&answers_path)
+        .current_dir(temp_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("spawn");
+
+    let inputs = ["staging", "", "", "", "1G", "2G", "", ""];
+    let input_blob = inputs.join("\n") + "\n";
+    child
+        .stdin
+        .as_mut()
+        .expect("stdin")
+        .write_all(input_blob.as_bytes())
+        .expect("write stdin");
+
+    let output = child.wait_with_output().expect("wait");
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let recorded = std::fs::read_to_string(&answers_path).expect("read answers");
+    assert!(recorded.contains("\"version\": \"1.0.0\""));
+    assert!(recorded.contains("\"xms\": \"1G\""));
+    // 新しいディレクトリでは上書きを尋ねていないため、回答として残さない
+    assert!(!recorded.contains("overwrite"), "answers: {recorded}");
+
+    let production_dir = TempDir::new().expect("temp dir");
+    let output = installer()
+        .arg("--non-interactive")
+        .arg("--answers")
+        .arg(&answers_path)
+        .current_dir(production_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let install_dir = production_dir.path().join("staging");
+    assert!(install_dir.join(jar_name).exists());
+    let sh = std::fs::read_to_string(install_dir.join("start.sh")).expect("read start.sh");
+    assert!(sh.contains("-Xms1G -Xmx2G"));
+
+    // 既にファイルがある場所へは、記録した回答だけでは上書きしない
+    let output = installer()
+        .arg("--non-interactive")
+        .arg("--answers")
+        .arg(&answers_path)
+        .current_dir(production_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--overwrite を指定してください"),
+        "stderr: {stderr}"
+    );
+}
+
+#[test]
+fn answers_file_rejects_changed_checksum() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    mock_single_version(&server, "1.0.0", "velocity-proxy-1.0.0.jar");
+    let answers_path = temp_dir.path().join("answers.json");
+    std::fs::write(
+        &answers_path,
+        format!(
+            r#"{{
+  "install_dir": "velocity",
+  "version": "1.0.0",
+  "sha256": "{}",
+  "xms": "256M",
+  "xmx": "512M",
+  "yes": true
+}}"#,
+            "0".repeat(64)
+        ),
+    )
+    .expect("write answers");
+
+    let output = installer()
+        .arg("--non-interactive")
+        .arg("--answers")
+        .arg(&answers_path)
+        .current_dir(temp_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("sha256 が指定された値と一致しません"),
+        "stderr: {stderr}"
+    );
+    assert!(!temp_dir.path().join("velocity").exists());
+}
+
+#[test]
+fn install_flow_selects_version_by_search_and_literal() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    mock_versions(
+        &server,
+        &[
+            ("3.9.0", "stable", "velocity-proxy-3.9.0.jar"),
+            ("3.10.0", "stable", "velocity-proxy-3.10.0.jar"),
+            (
+                "3.11.0-SNAPSHOT",
+                "beta",
+                "velocity-proxy-3.11.0-SNAPSHOT.jar",
+            ),
+        ],
+    );
+
+    let mut child = installer()
+        .args(["--dir", "velocity", "--xms", "256M", "--xmx", "512M"])
+        .current_dir(temp_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("spawn");
+
+    let inputs = [":beta", "2", ":all", "/3.9", "1", "n", "3.10", "y", "y"];
+    let input_blob = inputs.join("\n") + "\n";
+    child
+        .stdin
+        .as_mut()
+        .expect("stdin")
+        .write_all(input_blob.as_bytes())
+        .expect("write stdin");
+
+    let output = child.wait_with_output().expect("wait");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("[3.10.0]"), "stdout: {stdout}");
+    assert!(stdout.contains("種類: beta"));
+    assert!(stdout.contains("検索: 3.9"));
+
+    let install_dir = temp_dir.path().join("velocity");
+    assert!(install_dir.join("velocity-proxy-3.10.0.jar").exists());
+    assert!(!install_dir.join("velocity-proxy-3.9.0.jar").exists());
+}
+
+#[test]
+fn non_interactive_install_uses_local_jar() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let jar_bytes = b"local-velocity-jar";
+    let sha256 = format!("{:x}", Sha256::digest(jar_bytes));
+    let jar_name = "velocity-proxy-3.4.0.jar";
+    let local_jar = temp_dir.path().join(jar_name);
+    std::fs::write(&local_jar, jar_bytes).expect("write jar");
+
+    let output = installer()
+        .args(["--non-interactive", "--dir", "velocity", "--jar"])
+        .arg(&local_jar)
+        .args([
+            "--sha256", &sha256, "--xms", "256M", "--xmx", "512M", "--yes",
+        ])
+        .current_dir(temp_dir.path())
+        .env(
+            "MC_VELOCITY_INDEX_URL",
+            "http://127.0.0.1:9/unreachable.json",
+        )
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let install_dir = temp_dir.path().join("velocity");
+    assert_eq!(
+        std::fs::read(install_dir.join(jar_name)).expect("read jar"),
+        jar_bytes
+    );
+    let sh = std::fs::read_to_string(install_dir.join("start.sh")).expect("read start.sh");
+    assert!(sh.contains(jar_name));
+    assert_systemd_service(&install_dir);
+}
+
+#[test]
+fn local_jar_checksum_mismatch_fails() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let local_jar = temp_dir.path().join("velocity-proxy-3.4.0.jar");
+    std::fs::write(&local_jar, b"local-velocity-jar").expect("write jar");
+
+    let output = installer()
+        .args([
+            "--redownload-jar",
+            "--non-interactive",
+            "--replace-scripts=false",
+            "--dir",
+            "velocity",
+        ])
+        .arg("--jar")
+        .arg(&local_jar)
+        .args(["--sha256", &"0".repeat(64), "--yes"])
+        .current_dir(temp_dir.path())
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("チェックサム不一致"), "stderr: {stderr}");
+    assert!(
+        !temp_dir
+            .path()
+            .join("velocity")
+            .join("velocity-proxy-3.4.0.jar")
+            .exists()
+    );
+}
+
+/// `checksum` をそのままチェックサム欄に書いた 1 件だけのバージョン一覧を用意する。
+fn mock_checksummed_version(server: &MockServer, jar_name: &str, checksum: &str) {
+    let jar_path = format!("/{jar_name}");
+    server.mock(|when, then| {
+        when.method(GET).path(jar_path.as_str());
+        then.status(200).body(b"velocity-jar".as_slice());
+    });
+    let index_body = format!(
+        r#"{{"status":"ok","data":{{"1.0.0":{{"url":"{}","checksum":{checksum},"build":1,"type":"stable"}}}}}}"#,
+        server.url(jar_path.as_str())
+    );
+    server.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200).body(index_body);
+    });
+}
+
+#[test]
+fn install_verifies_sha512_and_reports_algorithm() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    let jar_name = "velocity-proxy-1.0.0.jar";
+    let sha512 = format!("{:x}", Sha512::digest(b"velocity-jar"));
+    mock_checksummed_version(
+        &server,
+        jar_name,
+        &format!(r#"{{"sha512":"{sha512}","sha256":"{}"}}"#, "0".repeat(64)),
+    );
+
+    let output = installer()
+        .args([
+            "--non-interactive",
+            "--dir",
+            "proxy",
+            "--version",
+            "1.0.0",
+            "--xms",
+            "1G",
+            "--xmx",
+            "2G",
+            "--yes",
+        ])
+        .current_dir(temp_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "stdout: {stdout}\nstderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains(&format!("- チェックサム: sha512 {sha512}")));
+    assert!(temp_dir.path().join("proxy").join(jar_name).exists());
+}
+
+#[test]
+fn sha1_only_version_requires_explicit_opt_in() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    let jar_name = "velocity-proxy-1.0.0.jar";
+    let sha1 = format!("{:x}", Sha1::digest(b"velocity-jar"));
+    mock_checksummed_version(
+        &server,
+        jar_name,
+        &format!(r#"{{"sha1":"{sha1}","sha256":null}}"#),
+    );
+    let run = |extra: &[&str]| {
+        installer()
+            .args([
+                "--redownload-jar",
+                "--non-interactive",
+                "--replace-scripts=false",
+                "--dir",
+                "proxy",
+                "--version",
+                "1.0.0",
+                "--yes",
+            ])
+            .args(extra)
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary")
+    };
+
+    let rejected = run(&[]);
+    assert!(!rejected.status.success());
+    let stderr = String::from_utf8_lossy(&rejected.stderr);
+    assert!(stderr.contains("--allow-weak-checksum"), "stderr: {stderr}");
+    assert!(!temp_dir.path().join("proxy").join(jar_name).exists());
+
+    let accepted = run(&["--allow-weak-checksum"]);
+    let stdout = String::from_utf8_lossy(&accepted.stdout);
+    assert!(
+        accepted.status.success(),
+        "stdout: {stdout}\nstderr: {}",
+        String::from_utf8_lossy(&accepted.stderr)
+    );
+    assert!(stdout.contains(&format!("- チェックサム: sha1 {sha1}")));
+    assert!(temp_dir.path().join("proxy").join(jar_name).exists());
+}
+
+#[test]
+fn signatures_are_verified_with_pinned_keys() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    let trusted = SigningKey::from_bytes(&[7; 32]);
+    let other = SigningKey::from_bytes(&[8; 32]);
+    let sign = |key: &SigningKey, data: &[u8]| STANDARD.encode(key.sign(data).to_bytes());
+
+    let jar_bytes = b"velocity-jar";
+    let jar_name = "velocity-proxy-1.0.0.jar";
+    let sha256 = format!("{:x}", Sha256::digest(jar_bytes));
+    server.mock(|when, then| {
+        when.method(GET).path(format!("/{jar_name}"));
+        then.status(200).body(jar_bytes.as_slice());
+    });
+    // 署名の無い jar（署名の必須化を確かめる）
+    server.mock(|when, then| {
+        when.method(GET).path("/unsigned.jar");
+        then.status(200).body(jar_bytes.as_slice());
+    });
+    let index_body = format!(
+        r#"{{"status":"ok","data":{{"1.0.0":{{"url":"{}","checksum":{{"sha256":"{sha256}"}},"build":1,"type":"stable"}},"1.0.1":{{"url":"{}","checksum":{{"sha256":"{sha256}"}},"build":2,"type":"stable"}}}}}}"#,
+        server.url(format!("/{jar_name}")),
+        server.url("/unsigned.jar")
+    );
+    let index_signature = sign(&trusted, index_body.as_bytes());
+    server.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200).body(&index_body);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/velocity.json.sig");
+        then.status(200).body(&index_signature);
+    });
+    let mut jar_signature = server.mock(|when, then| {
+        when.method(GET).path(format!("/{jar_name}.sig"));
+        then.status(200).body(sign(&trusted, jar_bytes));
+    });
+
+    let run = |version: &str, extra: &[&str]| {
+        installer()
+            .args([
+                "--redownload-jar",
+                "--non-interactive",
+                "--replace-scripts=false",
+                "--dir",
+                "proxy",
+                "--version",
+                version,
+                "--overwrite",
+                "--yes",
+            ])
+            .args(extra)
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .env(
+                "MC_VELOCITY_SIGNATURE_KEYS",
+                STANDARD.encode(trusted.verifying_key().to_bytes()),
+            )
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary")
+    };
+    let jar_path = temp_dir.path().join("proxy").join(jar_name);
+
+    let signed = run("1.0.0", &["--require-signature"]);
+    assert!(
+        signed.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&signed.stderr)
+    );
+    assert!(jar_path.exists());
+    std::fs::remove_file(&jar_path).expect("remove jar");
+
+    let unsigned = run("1.0.1", &["--require-signature"]);
+    assert!(!unsigned.status.success());
+    let stderr = String::from_utf8_lossy(&unsigned.stderr);
+    assert!(stderr.contains("署名がありません"), "stderr: {stderr}");
+    assert!(!temp_dir.path().join("proxy").join("unsigned.jar").exists());
+
+    let lenient = run("1.0.1", &[]);
+    assert!(lenient.status.success());
+    assert!(String::from_utf8_lossy(&lenient.stderr).contains("警告: 署名がありません"));
+
+    jar_signature.delete();
+    server.mock(|when, then| {
+        when.method(GET).path(format!("/{jar_name}.sig"));
+        then.status(200).body(sign(&other, jar_bytes));
+    });
+    let forged = run("1.0.0", &[]);
+    assert!(!forged.status.success());
+    let stderr = String::from_utf8_lossy(&forged.stderr);
+    assert!(stderr.contains("公開鍵と一致しません"), "stderr: {stderr}");
+    assert!(!jar_path.exists());
+}
+
+/// `MANIFEST.MF` だけを持つ jar（zip）を作る。
+fn jar_with_manifest(main_class: &str, implementation_version: &str) -> Vec<u8> {
+    let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    writer
+        .start_file("META-INF/MANIFEST.MF", SimpleFileOptions::default())
+        .expect("start manifest");
+    write!(
+        writer,
+        "Manifest-Version: 1.0\r\nMain-Class: {main_class}\r\nImplementation-Version: {implementation_version}\r\n\r\n"
+    )
+    .expect("write manifest");
+    writer.finish().expect("finish jar").into_inner()
+}
+
+#[test]
+fn jar_manifest_must_match_chosen_version() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    let good = jar_with_manifest(
+        "com.velocitypowered.proxy.Velocity",
+        "3.4.0 (git-abcdef12-b500)",
+    );
+    // 正しい名前で別の成果物を公開しているミラー
+    let wrong = jar_with_manifest("io.papermc.paperclip.Main", "1.21.4");
+    let mut entries = Vec::new();
+    for (version, jar_name, bytes) in [
+        ("3.4.0", "velocity-proxy-3.4.0.jar", &good),
+        ("3.5.0", "velocity-proxy-3.5.0.jar", &wrong),
+    ] {
+        server.mock(|when, then| {
+            when.method(GET).path(format!("/{jar_name}"));
+            then.status(200).body(bytes);
+        });
+        entries.push(format!(
+            r#""{version}":{{"url":"{}","checksum":{{"sha256":"{:x}"}},"build":1,"type":"stable"}}"#,
+            server.url(format!("/{jar_name}")),
+            Sha256::digest(bytes)
+        ));
+    }
+    let index_body = format!(r#"{{"status":"ok","data":{{{}}}}}"#, entries.join(","));
+    server.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200).body(index_body);
+    });
+    let run = |version: &str, extra: &[&str]| {
+        installer()
+            .args([
+                "--redownload-jar",
+                "--non-interactive",
+                "--replace-scripts=false",
+                "--dir",
+                "proxy",
+                "--version",
+                version,
+                "--overwrite",
+                "--yes",
+            ])
+            .args(extra)
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary")
+    };
+    let install_dir = temp_dir.path().join("proxy");
+
+    let matched = run("3.4.0", &["--jar-manifest-check", "fail"]);
+    assert!(matched.status.success());
+    assert!(
+        String::from_utf8_lossy(&matched.stdout)
+            .contains("jar のバージョン: 3.4.0 (git-abcdef12-b500)")
+    );
+    assert!(install_dir.join("velocity-proxy-3.4.0.jar").exists());
+
+    let rejected = run("3.5.0", &["--jar-manifest-check", "fail"]);
+    assert!(!rejected.status.success());
+    let stderr = String::from_utf8_lossy(&rejected.stderr);
+    assert!(stderr.contains("Main-Class が Velocity のものではありません"));
+    assert!(
+        stderr.contains("expected=3.5.0, actual=1.21.4"),
+        "stderr: {stderr}"
+    );
+    assert!(!install_dir.join("velocity-proxy-3.5.0.jar").exists());
+    assert!(!install_dir.join("velocity-proxy-3.5.0.jar.part").exists());
+
+    let warned = run("3.5.0", &[]);
+    assert!(warned.status.success());
+    assert!(
+        String::from_utf8_lossy(&warned.stderr).contains("警告: jar の中身が 3.5.0 と一致しません")
+    );
+    assert!(install_dir.join("velocity-proxy-3.5.0.jar").exists());
+}
+
+#[test]
+fn installed_version_is_detected_and_reported() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    mock_versions(
+        &server,
+        &[
+            ("1.0.0", "stable", "velocity-proxy-1.0.0.jar"),
+            ("1.1.0", "stable", "velocity-proxy-1.1.0.jar"),
+        ],
+    );
+    let run = |args: &[&str]| {
+        installer()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary")
+    };
+
+    let install = run(&[
+        "--non-interactive",
+        "--dir",
+        "proxy",
+        "--version",
+        "1.0.0",
+        "--xms",
+        "1G",
+        "--xmx",
+        "1G",
+        "--yes",
+    ]);
+    assert!(install.status.success());
+
+    // 置き換えるかどうかの回答が無ければ、取得する前にエラーにする
+    let unanswered = run(&[
+        "--redownload-jar",
+        "--non-interactive",
+        "--dir",
+        "proxy",
+        "--version",
+        "1.1.0",
+        "--overwrite",
+        "--yes",
+    ]);
+    assert!(!unanswered.status.success());
+    let stderr = String::from_utf8_lossy(&unanswered.stderr);
+    assert!(stderr.contains("--replace-scripts"), "stderr: {stderr}");
+    assert!(
+        !temp_dir
+            .path()
+            .join("proxy/velocity-proxy-1.1.0.jar")
+            .exists()
+    );
+
+    let redownload = run(&[
+        "--redownload-jar",
+        "--non-interactive",
+        "--replace-scripts=false",
+        "--dir",
+        "proxy",
+        "--version",
+        "1.1.0",
+        "--overwrite",
+        "--yes",
+    ]);
+    let stdout = String::from_utf8_lossy(&redownload.stdout);
+    assert!(redownload.status.success(), "stdout: {stdout}");
+    assert!(stdout.contains("インストール済みのバージョン: 1.0.0 (build 1)"));
+    assert!(stdout.contains("- バージョン: 1.0.0 (build 1) → 1.1.0 ("));
+
+    // スクリプトを置き換えていないので、起動するのは 1.0.0 のまま
+    let info = run(&["info", "--dir", "proxy", "--format", "json"]);
+    assert!(info.status.success());
+    let info: serde_json::Value = serde_json::from_slice(&info.stdout).expect("json");
+    assert_eq!(info["jar"], "velocity-proxy-1.0.0.jar");
+    assert_eq!(info["version"], "1.0.0");
+    assert_eq!(info["build"], 1);
+    assert_eq!(info["detected_from"], "state");
+
+    // 記録が無ければ jar のファイル名から読み取る
+    std::fs::remove_file(temp_dir.path().join("proxy/.mc-velocity-installer.json"))
+        .expect("remove state");
+    let info = run(&["info", "--dir", "proxy", "--format", "json"]);
+    let info: serde_json::Value = serde_json::from_slice(&info.stdout).expect("json");
+    assert_eq!(info["version"], "1.0.0");
+    assert_eq!(info["detected_from"], "file-name");
+
+    let missing = run(&["info", "--dir", "elsewhere"]);
+    assert!(!missing.status.success());
+    assert!(
+        String::from_utf8_lossy(&missing.stderr)
+            .contains("Velocity のインストールが見つかりません")
+    );
+}
+
+#[test]
+fn state_file_records_install_and_reports_drift() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    mock_versions(
+        &server,
+        &[
+            ("1.0.0", "stable", "velocity-proxy-1.0.0.jar"),
+            ("1.1.0", "stable", "velocity-proxy-1.1.0.jar"),
+        ],
+    );
+    let run = |args: &[&str]| {
+        installer()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary")
+    };
+    let install_dir = temp_dir.path().join("proxy");
+    let state_path = install_dir.join(".mc-velocity-installer.json");
+    let read_state = || -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(&state_path).expect("read state"))
+            .expect("state json")
+    };
+
+    let install = run(&[
+        "--non-interactive",
+        "--dir",
+        "proxy",
+        "--version",
+        "1.0.0",
+        "--xms",
+        "1G",
+        "--xmx",
+        "2G",
+        "--yes",
+    ]);
+    assert!(install.status.success());
+    let state = read_state();
+    assert_eq!(state["version"], "1.0.0");
+    assert_eq!(state["build"], 1);
+    assert_eq!(state["jar"], "velocity-proxy-1.0.0.jar");
+    assert_eq!(
+        state["sha256"],
+        format!("{:x}", Sha256::digest(b"velocity-jar"))
+    );
+    assert_eq!(state["index_url"], server.url("/velocity.json"));
+    assert_eq!(state["xms"], "1G");
+    assert_eq!(state["xmx"], "2G");
+    assert!(
+        state["service"]["exec_start"]
+            .as_str()
+            .expect("exec_start")
+            .ends_with("start.sh")
+    );
+    let installed_at = state["installed_at"].clone();
+
+    // メモリはフラグでも既存スクリプトでもなく記録から引き継ぐ
+    std::fs::write(
+        install_dir.join("start.sh"),
+        "exec java -jar \"velocity-proxy-1.0.0.jar\"\n",
+    )
+    .expect("edit start.sh");
+    let redownload = run(&[
+        "--redownload-jar",
+        "--non-interactive",
+        "--dir",
+        "proxy",
+        "--version",
+        "1.1.0",
+        "--replace-scripts",
+        "--overwrite",
+        "--yes",
+    ]);
+    assert!(redownload.status.success());
+    let start_sh = std::fs::read_to_string(install_dir.join("start.sh")).expect("start.sh");
+    assert!(start_sh.contains("-Xms1G -Xmx2G -jar \"velocity-proxy-1.1.0.jar\""));
+    let state = read_state();
+    assert_eq!(state["version"], "1.1.0");
+    assert_eq!(state["jar"], "velocity-proxy-1.1.0.jar");
+    assert_eq!(state["installed_at"], installed_at);
+
+    std::fs::write(
+        install_dir.join("start.sh"),
+        "exec java -Xms1G -Xmx4G -jar \"velocity-proxy-1.1.0.jar\"\n",
+    )
+    .expect("edit start.sh");
+    let info = run(&["info", "--dir", "proxy"]);
+    let stdout = String::from_utf8_lossy(&info.stdout);
+    assert!(info.status.success());
+    assert!(
+        stdout.contains("記録との差異: 起動メモリが記録と異なります"),
+        "stdout: {stdout}"
+    );
+}
+
+#[test]
+fn upgrade_switches_jar_and_rollback_restores_backup() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    mock_versions(
+        &server,
+        &[
+            ("1.0.0", "stable", "velocity-proxy-1.0.0.jar"),
+            ("1.1.0", "stable", "velocity-proxy-1.1.0.jar"),
+            (
+                "1.2.0-SNAPSHOT",
+                "snapshot",
+                "velocity-proxy-1.2.0-SNAPSHOT.jar",
+            ),
+        ],
+    );
+    let run = |args: &[&str]| {
+        installer()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary")
+    };
+    let install_dir = temp_dir.path().join("proxy");
+
+    let install = run(&[
+        "--non-interactive",
+        "--dir",
+        "proxy",
+        "--version",
+        "1.0.0",
+        "--xms",
+        "1G",
+        "--xmx",
+        "1G",
+        "--yes",
+    ]);
+    assert!(install.status.success());
+    let customized = std::fs::read_to_string(install_dir.join("start.sh"))
+        .expect("start.sh")
+        .replace("-jar", "-XX:+UseG1GC -jar");
+    std::fs::write(install_dir.join("start.sh"), &customized).expect("customize start.sh");
+    std::fs::write(
+        install_dir.join("velocity.toml"),
+        "bind = \"0.0.0.0:25577\"",
+    )
+    .expect("write toml");
+
+    let upgrade = run(&["upgrade", "--non-interactive", "--dir", "proxy", "--yes"]);
+    let stdout = String::from_utf8_lossy(&upgrade.stdout);
+    assert!(upgrade.status.success(), "stdout: {stdout}");
+    assert!(stdout.contains("- バージョン: 1.0.0 (build 1) → 1.1.0 ("));
+    let start_sh = std::fs::read_to_string(install_dir.join("start.sh")).expect("start.sh");
+    assert!(start_sh.contains("-Xms1G -Xmx1G -XX:+UseG1GC -jar \"velocity-proxy-1.1.0.jar\""));
+    assert!(
+        std::fs::read_to_string(install_dir.join("start.bat"))
+            .expect("start.bat")
+            .contains("-jar \"velocity-proxy-1.1.0.jar\"")
+    );
+    assert!(
+        install_dir
+            .join(".mc-velocity-installer-backup/velocity-proxy-1.0.0.jar")
+            .exists()
+    );
+    std::fs::write(install_dir.join("velocity.toml"), "changed").expect("edit toml");
+
+    let again = run(&["upgrade", "--non-interactive", "--dir", "proxy", "--yes"]);
+    assert!(again.status.success());
+    assert!(
+        String::from_utf8_lossy(&again.stdout)
+            .contains("更新はありません（インストール済み: 1.1.0 (build 2)）")
+    );
+
+    let rollback = run(&["rollback", "--non-interactive", "--dir", "proxy", "--yes"]);
+    let stdout = String::from_utf8_lossy(&rollback.stdout);
+    assert!(rollback.status.success(), "stdout: {stdout}");
+    assert!(stdout.contains("- バージョン: 1.1.0 (build 2) → 1.0.0 (build 1)"));
+    assert_eq!(
+        std::fs::read_to_string(install_dir.join("start.sh")).expect("start.sh"),
+        customized
+    );
+    assert_eq!(
+        std::fs::read_to_string(install_dir.join("velocity.toml")).expect("toml"),
+        "changed"
+    );
+    assert_eq!(
+        std::fs::read_to_string(install_dir.join("velocity.toml.before-upgrade"))
+            .expect("toml before upgrade"),
+        "bind = \"0.0.0.0:25577\""
+    );
+    assert!(
+        String::from_utf8_lossy(&rollback.stderr)
+            .contains("警告: velocity.toml はアップグレード後に変更されているため戻していません")
+    );
+    assert!(!install_dir.join(".mc-velocity-installer-backup").exists());
+
+    let nothing = run(&["rollback", "--non-interactive", "--dir", "proxy", "--yes"]);
+    assert!(!nothing.status.success());
+    assert!(String::from_utf8_lossy(&nothing.stderr).contains("バックアップがありません"));
+}
+
+#[test]
+fn failed_upgrade_keeps_previous_backup_and_state() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    mock_versions(
+        &server,
+        &[
+            ("1.0.0", "stable", "velocity-proxy-1.0.0.jar"),
+            ("1.1.0", "stable", "velocity-proxy-1.1.0.jar"),
+        ],
+    );
+    // 一覧にはあるが jar を取得できない取得元
+    let broken = MockServer::start();
+    let broken_index = format!(
+        r#"{{"status": "ok", "data": {{"1.2.0": {{"url": "{}", "checksum": {{"sha256": "{:x}"}}, "build": 3, "type": "stable"}}}}}}"#,
+        broken.url("/missing.jar"),
+        Sha256::digest(b"velocity-jar")
+    );
+    broken.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200).body(broken_index);
+    });
+    let run = |args: &[&str], index: &MockServer| {
+        installer()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", index.url("/velocity.json"))
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary")
+    };
+    let install_dir = temp_dir.path().join("proxy");
+    let backup_dir = install_dir.join(".mc-velocity-installer-backup");
+    let read_state = || -> serde_json::Value {
+        serde_json::from_str(
+            &std::fs::read_to_string(install_dir.join(".mc-velocity-installer.json"))
+                .expect("read state"),
+        )
+        .expect("state json")
+    };
+
+    let install = run(
+        &[
+            "--non-interactive",
+            "--dir",
+            "proxy",
+            "--version",
+            "1.0.0",
+            "--xms",
+            "1G",
+            "--xmx",
+            "1G",
+            "--yes",
+        ],
+        &server,
+    );
+    assert!(install.status.success());
+    let upgrade = run(
+        &[
+            "upgrade",
+            "--non-interactive",
+            "--dir",
+            "proxy",
+            "--version",
+            "1.1.0",
+            "--yes",
+        ],
+        &server,
+    );
+    assert!(upgrade.status.success());
+    assert!(backup_dir.join("velocity-proxy-1.0.0.jar").exists());
+
+    // 取得に失敗しても、直前の控えと記録はそのまま
+    let failed = run(
+        &["upgrade", "--non-interactive", "--dir", "proxy", "--yes"],
+        &broken,
+    );
+    assert!(!failed.status.success());
+    assert!(backup_dir.join("velocity-proxy-1.0.0.jar").exists());
+    assert!(!backup_dir.join("velocity-proxy-1.1.0.jar").exists());
+    assert!(!install_dir.join(".mc-velocity-installer-upgrade").exists());
+    assert_eq!(read_state()["version"], "1.1.0");
+
+    // 書き換えられない起動スクリプトがあれば、jar も記録も変えずにエラーにする
+    let start_sh = std::fs::read_to_string(install_dir.join("start.sh")).expect("start.sh");
+    std::fs::write(
+        install_dir.join("start.bat"),
+        "@echo off\r\necho custom\r\n",
+    )
+    .expect("edit start.bat");
+    let unreadable = run(
+        &[
+            "upgrade",
+            "--non-interactive",
+            "--dir",
+            "proxy",
+            "--version",
+            "1.0.0",
+            "--yes",
+        ],
+        &server,
+    );
+    assert!(!unreadable.status.success());
+    assert!(String::from_utf8_lossy(&unreadable.stderr).contains("start.bat"));
+    assert_eq!(
+        std::fs::read_to_string(install_dir.join("start.sh")).expect("start.sh"),
+        start_sh
+    );
+    assert!(backup_dir.join("velocity-proxy-1.0.0.jar").exists());
+    assert_eq!(read_state()["version"], "1.1.0");
+}
+
+#[test]
+fn check_updates_exit_code_reflects_available_update() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    mock_versions(
+        &server,
+        &[
+            ("1.0.0", "stable", "velocity-proxy-1.0.0.jar"),
+            ("1.1.0", "stable", "velocity-proxy-1.1.0.jar"),
+            (
+                "1.2.0-SNAPSHOT",
+                "snapshot",
+                "velocity-proxy-1.2.0-SNAPSHOT.jar",
+            ),
+        ],
+    );
+    let run = |args: &[&str]| {
+        installer()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary")
+    };
+    let install = run(&[
+        "--non-interactive",
+        "--dir",
+        "proxy",
+        "--version",
+        "1.1.0",
+        "--xms",
+        "1G",
+        "--xmx",
+        "1G",
+        "--yes",
+    ]);
+    assert!(install.status.success());
+
+    let stable = run(&["check-updates", "--dir", "proxy", "--kind", "stable"]);
+    let stdout = String::from_utf8_lossy(&stable.stdout);
+    assert_eq!(stable.status.code(), Some(0), "stdout: {stdout}");
+    assert!(stdout.contains("インストール済み: 1.1.0 (build 2)"));
+    assert!(stdout.contains("最新です。"));
+
+    let any = run(&["check-updates", "--dir", "proxy", "--format", "json"]);
+    assert_eq!(any.status.code(), Some(2));
+    let report: serde_json::Value = serde_json::from_slice(&any.stdout).expect("json");
+    assert_eq!(report["installed"]["version"], "1.1.0");
+    assert_eq!(report["latest"]["version"], "1.2.0-SNAPSHOT");
+    assert_eq!(report["latest"]["build"], 3);
+    assert_eq!(report["update_available"], true);
+
+    let missing = run(&["check-updates", "--dir", "elsewhere"]);
+    assert_eq!(missing.status.code(), Some(1));
+    assert!(
+        String::from_utf8_lossy(&missing.stderr)
+            .contains("Velocity のインストールが見つかりません")
+    );
+}
+
+#[test]
+fn update_timer_is_generated_when_scheduled() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    mock_single_version(&server, "1.0.0", "velocity-proxy-1.0.0.jar");
+
+    let output = installer()
+        .current_dir(temp_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .args([
+            "--non-interactive",
+            "--dir",
+            "proxy",
+            "--version",
+            "1.0.0",
+            "--xms",
+            "1G",
+            "--xmx",
+            "1G",
+            "--update-schedule",
+            "Mon *-*-* 04:00",
+            "--yes",
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stdout: {stdout}");
+    assert!(stdout.contains("- 自動更新: Mon *-*-* 04:00（type stable の最新に更新）"));
+
+    let install_dir = temp_dir.path().join("proxy");
+    let timer =
+        std::fs::read_to_string(install_dir.join("velocity-update.timer")).expect("read timer");
+    assert!(timer.contains("OnCalendar=Mon *-*-* 04:00\n"));
+    let service = std::fs::read_to_string(install_dir.join("velocity-update.service"))
+        .expect("read update service");
+    assert!(service.contains(&format!(
+        "Environment=\"MC_VELOCITY_INDEX_URL={}\"",
+        server.url("/velocity.json")
+    )));
+    assert!(service.contains("check-updates --dir"));
+    assert!(service.contains("--kind stable; case $$? in 2) exit 0 ;;"));
+    assert!(service.contains("upgrade --non-interactive --yes --quiet --dir"));
+    assert!(service.contains("--version latest-stable\n"));
+    assert!(service.contains("ExecStartPost=+/bin/systemctl try-restart velocity.service\n"));
+
+    let unscheduled = temp_dir.path().join("plain");
+    let output = installer()
+        .current_dir(temp_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .args([
+            "--non-interactive",
+            "--dir",
+            "plain",
+            "--version",
+            "1.0.0",
+            "--xms",
+            "1G",
+            "--xmx",
+            "1G",
+            "--yes",
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+    assert!(output.status.success());
+    assert!(!unscheduled.join("velocity-update.timer").exists());
+
+    // インストール時の設定ファイル・フラグ・環境変数を自動更新に引き継ぐ
+    let config_path = temp_dir.path().join("installer.toml");
+    std::fs::write(&config_path, "").expect("write config");
+    let output = installer()
+        .current_dir(temp_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+        .env("MC_VELOCITY_JAR_MIRRORS", "https://mirror.example/jars/")
+        .args([
+            "--non-interactive",
+            "--dir",
+            "configured",
+            "--version",
+            "1.0.0",
+            "--xms",
+            "1G",
+            "--xmx",
+            "1G",
+            "--config",
+            "installer.toml",
+            "--skip-invalid-entries",
+            "--update-schedule",
+            "daily",
+            "--update-kind",
+            "beta",
+            "--yes",
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let service =
+        std::fs::read_to_string(temp_dir.path().join("configured/velocity-update.service"))
+            .expect("read update service");
+    let options = format!("--config {} --skip-invalid-entries", config_path.display());
+    assert!(
+        service.contains("Environment=\"MC_VELOCITY_JAR_MIRRORS=https://mirror.example/jars/\"\n")
+    );
+    assert!(service.contains(&format!("--kind beta {options}; case")));
+    assert!(service.contains(&format!("--version latest-beta {options}\n")));
+
+    // ユニットファイルに書く値は検証する
+    for (schedule, kind) in [
+        ("daily", "stable; rm -rf /"),
+        ("daily\nExecStartPre=/bin/false", "stable"),
+    ] {
+        let output = installer()
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .args([
+                "--non-interactive",
+                "--dir",
+                "rejected",
+                "--version",
+                "1.0.0",
+                "--xms",
+                "1G",
+                "--xmx",
+                "1G",
+                "--update-schedule",
+                schedule,
+                "--update-kind",
+                kind,
+                "--yes",
+            ])
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary");
+        assert!(!output.status.success(), "{schedule:?} {kind:?}");
+        assert!(
+            !temp_dir
+                .path()
+                .join("rejected/velocity-update.service")
+                .exists()
+        );
+    }
+}
+
+#[test]
+fn old_jars_are_pruned_by_retention_and_prune_mode() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    mock_versions(
+        &server,
+        &[
+            ("1.0.0", "stable", "velocity-proxy-1.0.0.jar"),
+            ("1.1.0", "stable", "velocity-proxy-1.1.0.jar"),
+            ("1.2.0", "stable", "velocity-proxy-1.2.0.jar"),
+        ],
+    );
+    let run = |args: &[&str]| {
+        installer()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary")
+    };
+    let install_dir = temp_dir.path().join("proxy");
+    let switch_to = |version: &str, extra: &[&str]| {
+        let mut args = vec![
+            "--redownload-jar",
+            "--non-interactive",
+            "--dir",
+            "proxy",
+            "--version",
+            version,
+            "--replace-scripts",
+            "--overwrite",
+            "--yes",
+        ];
+        args.extend_from_slice(extra);
+        let output = run(&args);
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    };
+
+    let install = run(&[
+        "--non-interactive",
+        "--dir",
+        "proxy",
+        "--version",
+        "1.0.0",
+        "--xms",
+        "1G",
+        "--xmx",
+        "1G",
+        "--yes",
+    ]);
+    assert!(install.status.success());
+    std::fs::write(install_dir.join("tool.jar"), b"not velocity").expect("write tool jar");
+    // 名前が velocity- で始まっても、MANIFEST.MF が別物なら対象にしない
+    std::fs::write(
+        install_dir.join("velocity-plugin.jar"),
+        jar_with_manifest("io.example.Plugin", "1.0"),
+    )
+    .expect("write plugin jar");
+
+    switch_to("1.1.0", &[]);
+    assert!(install_dir.join("velocity-proxy-1.0.0.jar").exists());
+
+    let output = switch_to("1.2.0", &["--keep-jars", "1"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("古い jar を削除しました: "));
+    assert!(!install_dir.join("velocity-proxy-1.0.0.jar").exists());
+    assert!(install_dir.join("velocity-proxy-1.1.0.jar").exists());
+    assert!(install_dir.join("velocity-proxy-1.2.0.jar").exists());
+
+    let kept = run(&[
+        "prune",
+        "--non-interactive",
+        "--dir",
+        "proxy",
+        "--keep-jars",
+        "1",
+        "--yes",
+    ]);
+    assert!(kept.status.success());
+    assert!(String::from_utf8_lossy(&kept.stdout).contains("削除できる jar はありません。"));
+    assert!(install_dir.join("velocity-proxy-1.1.0.jar").exists());
+
+    let prune = run(&["prune", "--non-interactive", "--dir", "proxy", "--yes"]);
+    let stdout = String::from_utf8_lossy(&prune.stdout);
+    assert!(prune.status.success(), "stdout: {stdout}");
+    assert!(stdout.contains("起動スクリプトが参照する jar: velocity-proxy-1.2.0.jar"));
+    assert!(stdout.contains("  - velocity-proxy-1.1.0.jar ("));
+    assert!(!install_dir.join("velocity-proxy-1.1.0.jar").exists());
+    assert!(install_dir.join("velocity-proxy-1.2.0.jar").exists());
+    assert!(install_dir.join("tool.jar").exists());
+    assert!(install_dir.join("velocity-plugin.jar").exists());
+
+    let again = run(&["prune", "--non-interactive", "--dir", "proxy", "--yes"]);
+    assert!(again.status.success());
+    assert!(String::from_utf8_lossy(&again.stdout).contains("削除できる jar はありません。"));
+
+    let empty = run(&["prune", "--non-interactive", "--dir", "elsewhere", "--yes"]);
+    assert!(!empty.status.success());
+}
+
+#[test]
+fn install_flow_uses_local_index_directory() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let mirror = temp_dir.path().join("mirror");
+    std::fs::create_dir_all(mirror.join("jars")).expect("create mirror");
+    let jar_bytes = b"mirrored-velocity-jar";
+    let jar_name = "velocity-proxy-3.4.0.jar";
+    std::fs::write(mirror.join("jars").join(jar_name), jar_bytes).expect("write jar");
+    std::fs::write(
+        mirror.join("velocity.json"),
+        format!(
+            r#"{{
+  "status": "ok",
+  "data": {{
+    "3.4.0": {{
+      "url": "jars/{}",
+      "checksum": {{ "sha256": "{:x}" }},
+      "type": "stable"
+    }}
+  }}
+}}"#,
+            jar_name,
+            Sha256::digest(jar_bytes)
+        ),
+    )
+    .expect("write index");
+
+    let output = installer()
+        .args([
+            "--non-interactive",
+            "--dir",
+            "velocity",
+            "--version",
+            "latest",
+            "--xms",
+            "256M",
+            "--xmx",
+            "512M",
+            "--yes",
+        ])
+        .current_dir(temp_dir.path())
+        .env("MC_VELOCITY_INDEX_URL", mirror.join("velocity.json"))
+        .stdin(Stdio::null())
+        .output()
+        .expect("run binary");
+    assert!(
+        output.status.success(),
+        "stdout: {}\nstderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let install_dir = temp_dir.path().join("velocity");
+    assert_eq!(
+        std::fs::read(install_dir.join(jar_name)).expect("read jar"),
+        jar_bytes
+    );
+}
+
+#[test]
+fn download_progress_is_printed_unless_quiet() {
+    let server = MockServer::start();
+    let jar_name = "velocity-proxy-1.0.0.jar";
+    mock_single_version(&server, "1.0.0", jar_name);
+
+    for quiet in [false, true] {
+        let temp_dir = TempDir::new().expect("temp dir");
+        let mut command = installer();
+        command.args([
+            "--non-interactive",
+            "--dir",
+            "velocity",
+            "--version",
+            "1.0.0",
+            "--xms",
+            "256M",
+            "--xmx",
+            "512M",
+            "--yes",
+        ]);
+        if quiet {
+            command.arg("--quiet");
+        }
+        let output = command
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "stdout: {stdout}");
+        assert_eq!(
+            stdout.contains("12 B / 12 B (100%)"),
+            !quiet,
+            "stdout: {stdout}"
+        );
+        assert_eq!(
+            stdout.contains("ダウンロード中: "),
+            !quiet,
+            "stdout: {stdout}"
+        );
+        assert!(temp_dir.path().join("velocity").join(jar_name).exists());
+    }
+}
+
+#[test]
+fn quiet_hides_mirror_fallback_status() {
+    let server = MockServer::start();
+    let jar_bytes = b"velocity-jar";
+    let jar_name = "velocity-proxy-1.0.0.jar";
+    // 一覧の URL には jar が無く、ミラーから取得する
+    server.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200).body(format!(
+            r#"{{"status":"ok","data":{{"1.0.0":{{"url":"{}","checksum":{{"sha256":"{:x}"}},"build":1,"type":"stable"}}}}}}"#,
+            server.url(format!("/gone/{jar_name}")),
+            Sha256::digest(jar_bytes)
+        ));
+    });
+    server.mock(|when, then| {
+        when.method(GET).path(format!("/mirror/{jar_name}"));
+        then.status(200).body(jar_bytes.as_slice());
+    });
+
+    for quiet in [false, true] {
+        let temp_dir = TempDir::new().expect("temp dir");
+        let mut command = installer();
+        command.args([
+            "--non-interactive",
+            "--dir",
+            "velocity",
+            "--version",
+            "1.0.0",
+            "--xms",
+            "256M",
+            "--xmx",
+            "512M",
+            "--yes",
+        ]);
+        if quiet {
+            command.arg("--quiet");
+        }
+        let output = command
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .env("MC_VELOCITY_JAR_MIRRORS", server.url("/mirror/"))
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "stderr: {stderr}");
+        assert!(temp_dir.path().join("velocity").join(jar_name).exists());
+        // 取得元の失敗は警告として常に標準エラー出力に出す
+        assert!(
+            stderr.contains(&format!(
+                "警告: {} から取得できませんでした",
+                server.url(format!("/gone/{jar_name}"))
+            )),
+            "stderr: {stderr}"
+        );
+        assert!(!stdout.contains("取得できませんでした"), "stdout: {stdout}");
+        // 取得の状況は --quiet では標準出力に出さない
+        assert_eq!(
+            stdout.contains(&format!("jar の取得元: {}", server.url("/mirror/"))),
+            !quiet,
+            "stdout: {stdout}"
+        );
+        if quiet {
+            assert!(!stdout.contains("/gone/"), "stdout: {stdout}");
+            assert!(!stdout.contains("/mirror/"), "stdout: {stdout}");
+        }
+    }
+}
+
+#[test]
+fn jar_cache_is_shared_between_installs() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let cache_dir = temp_dir.path().join("cache");
+    let server = MockServer::start();
+    let jar_bytes = b"velocity-jar";
+    let sha256 = format!("{:x}", Sha256::digest(jar_bytes));
+    let jar_mock = server.mock(|when, then| {
+        when.method(GET).path("/velocity-proxy-1.0.0.jar");
+        then.status(200).body(jar_bytes.as_slice());
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/velocity.json");
+        then.status(200).body(format!(
+            r#"{{"status":"ok","data":{{"1.0.0":{{"url":"{}","checksum":{{"sha256":"{sha256}"}},"build":1,"type":"stable"}}}}}}"#,
+            server.url("/velocity-proxy-1.0.0.jar")
+        ));
+    });
+
+    let run = |args: &[&str]| {
+        let output = Command::new(bin_path())
+            .args(args)
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .env("MC_VELOCITY_CACHE_DIR", &cache_dir)
+            .env_remove("MC_VELOCITY_NO_CACHE")
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary");
+        assert!(
+            output.status.success(),
+            "stdout: {}\nstderr: {}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+    for dir in ["proxy-a", "proxy-b"] {
+        run(&[
+            "--non-interactive",
+            "--dir",
+            dir,
+            "--version",
+            "1.0.0",
+            "--xms",
+            "1G",
+            "--xmx",
+            "1G",
+            "--yes",
+        ]);
+        let jar = temp_dir.path().join(dir).join("velocity-proxy-1.0.0.jar");
+        assert_eq!(std::fs::read(jar).expect("read jar"), jar_bytes);
+    }
+    jar_mock.assert_hits(1);
+
+    let listed = run(&["cache", "list"]);
+    assert!(listed.contains(&sha256[..12]));
+    assert!(listed.contains("velocity-proxy-1.0.0.jar"));
+
+    let cleared = run(&["cache", "clear"]);
+    assert!(cleared.contains("1 件"));
+    assert!(!cache_dir.join("jars").exists());
+    assert!(!cache_dir.join("index").exists());
+}
+
+#[test]
+fn custom_jvm_flags_survive_script_regeneration() {
+    let temp_dir = TempDir::new().expect("temp dir");
+    let server = MockServer::start();
+    mock_versions(
+        &server,
+        &[
+            ("1.0.0", "stable", "velocity-proxy-1.0.0.jar"),
+            ("1.1.0", "stable", "velocity-proxy-1.1.0.jar"),
+        ],
+    );
+    let run = |args: &[&str]| {
+        installer()
+            .args(args)
+            .current_dir(temp_dir.path())
+            .env("MC_VELOCITY_INDEX_URL", server.url("/velocity.json"))
+            .stdin(Stdio::null())
+            .output()
+            .expect("run binary")
+    };
+    let install_dir = temp_dir.path().join("proxy");
+    let install = run(&[
+        "--non-interactive",
+        "--dir",
+        "proxy",
+        "--version",
+        "1.0.0",
+        "--xms",
+        "1G",
+        "--xmx",
+        "1G",
+        "--yes",
+    ]);
+    assert!(install.status.success());
+
+    // 運用で追加した GC フラグ・システムプロパティ・引数
+    std::fs::write(
+        install_dir.join("start.sh"),
+        "#!/usr/bin/env sh\nset -e\nexport TZ=Asia/Tokyo\nexec java -Xms1G -Xmx1G -XX:+UseG1GC \\\n  -Dvelocity.packet-decode-logging=true -jar \"velocity-proxy-1.0.0.jar\" --port 25577\n",
+    )
+    .expect("edit start.sh");
+    std::fs::write(
+        install_dir.join("start.bat"),
+        "@echo off\r\njava -Xms1G -Xmx1G -XX:+UseG1GC -jar \"velocity-proxy-1.0.0.jar\" %*\r\n",
+    )
+    .expect("edit start.bat");
+
+    let redownload = run(&[
+        "--redownload-jar",
+        "--non-interactive",
+        "--dir",
+        "proxy",
+        "--version",
+        "1.0.0",
+        "--replace-scripts",
+        "--xms",
+        "2G",
+        "--xmx",
+        "4G",
+        "--overwrite",
+        "--yes",
+    ]);
+    assert!(
+        redownload.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&redownload.stderr)
+    );
+    assert_eq!(
+        std::fs::read_to_string(install_dir.join("start.sh")).expect("start.sh"),
+        "#!/usr/bin/env sh\nset -e\nexport TZ=Asia/Tokyo\nexec java -Xms2G -Xmx4G -XX:+UseG1GC \\\n  -Dvelocity.packet-decode-logging=true -jar \"velocity-proxy-1.0.0.jar\" --port 25577\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(install_dir.join("start.bat")).expect("start.bat"),
+        "@echo off\r\njava -Xms2G -Xmx4G -XX:+UseG1GC -jar \"velocity-proxy-1.0.0.jar\" %*\r\n"
+    );
+
+    let upgrade = run(&["upgrade", "--non-interactive", "--dir", "proxy", "--yes"]);
+    assert!(upgrade.status.success());
+    let start_sh = std::fs::read_to_string(install_dir.join("start.sh")).expect("start.sh");
+    assert!(start_sh.contains(
+        "exec java -Xms2G -Xmx4G -XX:+UseG1GC \\\n  -Dvelocity.packet-decode-logging=true -jar \"velocity-proxy-1.1.0.jar\" --port 25577\n"
+    ));
+    assert!(start_sh.contains("export TZ=Asia/Tokyo\n"));
+}